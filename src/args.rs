@@ -1,18 +1,69 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
-use solana_program::pubkey::Pubkey;
+use clap::{Parser, Subcommand, ValueEnum};
+use mpl_migration_validator::state::UnlockMethod;
+use solana_program::{hash::Hash, pubkey::Pubkey};
+
+use crate::compute_budget::PriorityFee;
 
 #[derive(Parser)]
 #[clap(author, version, about)]
 pub struct Args {
-    /// Path to the keypair file.
+    /// Path to the keypair file of the collection update authority.
     #[arg(short, long, global = true)]
     pub keypair_path: Option<PathBuf>,
 
-    /// RPC URL for the Solana cluster.
-    #[arg(short, long, global = true)]
-    pub rpc_url: Option<String>,
+    /// Path to a keypair file paying transaction fees and rent, so the authority
+    /// only has to sign. Defaults to the authority keypair.
+    #[arg(long, global = true)]
+    pub fee_payer: Option<PathBuf>,
+
+    /// RPC URL for the Solana cluster. Repeat the flag or separate URLs with commas
+    /// to spread requests over several endpoints with failover. Defaults to
+    /// `rpc_urls` in ~/.config/goose/config.yml, then `json_rpc_url` in the
+    /// Solana CLI config.
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "GOOSE_RPC_URLS",
+        value_delimiter = ','
+    )]
+    pub rpc_url: Vec<String>,
+
+    /// Priority fee in micro-lamports per compute unit, or 'auto' to derive it from recent fees.
+    #[arg(long, global = true)]
+    pub priority_fee: Option<PriorityFee>,
+
+    /// Compute unit limit to request for each transaction.
+    #[arg(long, global = true)]
+    pub compute_units: Option<u32>,
+
+    /// Build and sign transactions but only simulate them instead of sending.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Durable nonce account to use instead of a recent blockhash, so signed
+    /// transactions don't expire.
+    #[arg(long, global = true)]
+    pub nonce_account: Option<Pubkey>,
+
+    /// Authority of the nonce account. Defaults to the fee payer.
+    #[arg(long, global = true, requires = "nonce_account")]
+    pub nonce_authority: Option<Pubkey>,
+
+    /// Squads multisig whose vault is the collection authority. Init, update,
+    /// start and cancel then create a vault transaction proposal instead.
+    #[arg(long, global = true)]
+    pub multisig: Option<Pubkey>,
+
+    /// Index of the multisig vault acting as the authority.
+    #[arg(long, global = true, default_value = "0")]
+    pub vault_index: u8,
+
+    /// Print human readable text, or a single JSON object for scripts.
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    pub output: OutputFormat,
 
     #[clap(subcommand)]
     pub command: Commands,
@@ -25,9 +76,15 @@ pub enum Commands {
         #[arg(short, long)]
         collection_mint: Pubkey,
 
-        /// Unlock method for the collection: Timed or Vote.
-        #[arg(short = 'm', long, default_value = "Timed")]
-        unlock_method: String,
+        /// Unlock method for the collection.
+        #[arg(
+            short = 'm',
+            long,
+            value_enum,
+            ignore_case = true,
+            default_value = "timed"
+        )]
+        unlock_method: UnlockMethodArg,
 
         /// Number of items in the collection.
         #[arg(short, long)]
@@ -46,9 +103,15 @@ pub enum Commands {
         #[arg(short, long)]
         collection_mint: Pubkey,
 
-        /// Unlock method for the collection: Timed or Vote.
-        #[arg(short = 'm', long, default_value = "Timed")]
-        unlock_method: String,
+        /// Unlock method for the collection.
+        #[arg(
+            short = 'm',
+            long,
+            value_enum,
+            ignore_case = true,
+            default_value = "timed"
+        )]
+        unlock_method: UnlockMethodArg,
 
         /// Number of items in the collection.
         #[arg(short, long)]
@@ -112,21 +175,220 @@ pub enum Commands {
         #[arg(short, long)]
         collection_mint: Pubkey,
 
-        /// Mint list
+        /// Mint list: JSON array, newline-delimited, CSV or Sugar cache file.
         #[arg(short, long)]
         mint_list: PathBuf,
 
+        /// CSV column holding the mints, by header name or 0-based index.
+        #[arg(long)]
+        mint_column: Option<String>,
+
+        /// Skip invalid mint list entries instead of refusing to run.
+        #[arg(long)]
+        skip_invalid: bool,
+
         /// Maxiumum number of parallel requests to make to the RPC server.
         #[arg(short, long, default_value = "100")]
         batch_size: usize,
+
+        /// Resume a previous run, skipping mints already recorded as migrated in the journal.
+        #[arg(long)]
+        resume: bool,
+
+        /// Maximum number of attempts per item for transient errors.
+        #[arg(long, default_value = "3")]
+        max_attempts: u32,
+
+        /// Base delay in milliseconds for the exponential retry backoff.
+        #[arg(long, default_value = "500")]
+        retry_delay_ms: u64,
+
+        /// How to send migrate transactions: wait for each confirmation, or send
+        /// and confirm in batches in the background.
+        #[arg(long, value_enum, default_value = "confirm")]
+        send_mode: SendMode,
+
+        /// Wait for a timed migration to unlock before migrating.
+        #[arg(long)]
+        wait_for_unlock: bool,
+
+        /// Maximum number of RPC requests per second, shared across all parallel tasks.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        max_rps: Option<u32>,
+    },
+    ValidateList {
+        /// Mint list: JSON array, newline-delimited, CSV or Sugar cache file.
+        #[arg(short, long)]
+        mint_list: PathBuf,
+
+        /// CSV column holding the mints, by header name or 0-based index.
+        #[arg(long)]
+        mint_column: Option<String>,
+
+        /// Write the valid, deduplicated mints to this file as a JSON array.
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    Snapshot {
+        /// Mint of the collection parent NFT.
+        #[arg(short, long)]
+        collection_mint: Pubkey,
+
+        /// Only scan items whose first creator is this address. Strongly recommended on mainnet.
+        #[arg(long)]
+        first_creator: Option<Pubkey>,
+
+        /// Where to write the mint list. Defaults to <collection_mint>_mint_list.json.
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
     },
     Check {
-        /// Mint list
+        /// Mint list: JSON array, newline-delimited, CSV or Sugar cache file.
+        #[arg(short, long)]
+        mint_list: PathBuf,
+
+        /// CSV column holding the mints, by header name or 0-based index.
+        #[arg(long)]
+        mint_column: Option<String>,
+
+        /// Skip invalid mint list entries instead of refusing to run.
+        #[arg(long)]
+        skip_invalid: bool,
+
+        /// Maxiumum number of parallel requests to make to the RPC server.
+        #[arg(short, long, default_value = "100")]
+        batch_size: usize,
+
+        /// Maximum number of RPC requests per second, shared across all parallel tasks.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        max_rps: Option<u32>,
+    },
+    Run {
+        /// Mint of the collection parent NFT.
+        #[arg(short, long)]
+        collection_mint: Pubkey,
+
+        /// Mint list: JSON array, newline-delimited, CSV or Sugar cache file.
+        #[arg(short, long)]
+        mint_list: PathBuf,
+
+        /// CSV column holding the mints, by header name or 0-based index.
+        #[arg(long)]
+        mint_column: Option<String>,
+
+        /// Skip invalid mint list entries instead of refusing to run.
+        #[arg(long)]
+        skip_invalid: bool,
+
+        /// Unlock method to initialize the migration state with.
+        #[arg(long, value_enum, ignore_case = true)]
+        unlock_method: Option<UnlockMethodArg>,
+
+        /// Number of items in the collection, to initialize the migration state with.
+        #[arg(short, long)]
+        size: Option<u32>,
+
+        /// Maxiumum number of parallel requests to make to the RPC server.
+        #[arg(short, long, default_value = "100")]
+        batch_size: usize,
+
+        /// Maximum number of attempts per item for transient errors.
+        #[arg(long, default_value = "3")]
+        max_attempts: u32,
+
+        /// Base delay in milliseconds for the exponential retry backoff.
+        #[arg(long, default_value = "500")]
+        retry_delay_ms: u64,
+
+        /// How to send migrate transactions: wait for each confirmation, or send
+        /// and confirm in batches in the background.
+        #[arg(long, value_enum, default_value = "confirm")]
+        send_mode: SendMode,
+
+        /// Maximum number of RPC requests per second, shared across all parallel tasks.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        max_rps: Option<u32>,
+    },
+    SignMsg {
+        /// Message to sign, as printed by init-msg or update-msg, or a file holding it.
+        message: String,
+
+        /// Recent blockhash to set on the message before signing. Every signer
+        /// must use the same one. Not allowed for messages using a durable nonce.
+        #[arg(long)]
+        blockhash: Option<Hash>,
+
+        /// Where to write the signature. Defaults to <signer>_signature.json.
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    Submit {
+        /// Signature files written by sign-msg, one per signer.
+        #[arg(required = true)]
+        signature_files: Vec<PathBuf>,
+    },
+    Inspect {
+        /// Serialized transaction or message, bs58 or base64, or a file holding it.
+        input: String,
+    },
+    CreateNonce {
+        /// Keypair for the new nonce account. Defaults to a freshly generated one,
+        /// saved as <pubkey>_nonce.json.
+        #[arg(long)]
+        nonce_keypair: Option<PathBuf>,
+
+        /// Authority allowed to advance the nonce. Defaults to the fee payer.
+        #[arg(short, long)]
+        authority: Option<Pubkey>,
+    },
+    Owners {
+        /// Mint list: JSON array, newline-delimited, CSV or Sugar cache file.
         #[arg(short, long)]
         mint_list: PathBuf,
 
+        /// CSV column holding the mints, by header name or 0-based index.
+        #[arg(long)]
+        mint_column: Option<String>,
+
+        /// Skip invalid mint list entries instead of refusing to run.
+        #[arg(long)]
+        skip_invalid: bool,
+
         /// Maxiumum number of parallel requests to make to the RPC server.
         #[arg(short, long, default_value = "100")]
         batch_size: usize,
+
+        /// Where to write the report. Defaults to owners.json.
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
     },
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum UnlockMethodArg {
+    Timed,
+    Vote,
+}
+
+impl From<UnlockMethodArg> for UnlockMethod {
+    fn from(unlock_method: UnlockMethodArg) -> Self {
+        match unlock_method {
+            UnlockMethodArg::Timed => UnlockMethod::Timed,
+            UnlockMethodArg::Vote => UnlockMethod::Vote,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SendMode {
+    /// Send and confirm each transaction before moving on to the next item.
+    Confirm,
+    /// Send without waiting and confirm via batched `getSignatureStatuses` polling.
+    Batch,
+}
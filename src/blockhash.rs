@@ -0,0 +1,92 @@
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::hash::Hash;
+use tokio::task::JoinHandle;
+
+pub const BLOCKHASH_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug)]
+pub struct LatestBlockhash {
+    pub hash: Hash,
+    pub last_valid_block_height: u64,
+}
+
+/// Latest blockhash shared between concurrent transaction senders so each one
+/// doesn't have to fetch its own.
+#[derive(Clone)]
+pub struct BlockhashCache {
+    latest: Arc<RwLock<LatestBlockhash>>,
+    failures: Arc<Mutex<RefreshFailures>>,
+}
+
+/// Background refreshes that failed, leaving the previous blockhash in place.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RefreshFailures {
+    pub count: u64,
+    pub last_error: Option<String>,
+}
+
+impl BlockhashCache {
+    pub fn get(&self) -> LatestBlockhash {
+        *self.latest.read().unwrap()
+    }
+
+    pub fn hash(&self) -> Hash {
+        self.get().hash
+    }
+
+    pub fn refresh_failures(&self) -> RefreshFailures {
+        self.failures.lock().unwrap().clone()
+    }
+}
+
+async fn fetch_latest(client: &RpcClient) -> Result<LatestBlockhash> {
+    let (hash, last_valid_block_height) = client
+        .get_latest_blockhash_with_commitment(client.commitment())
+        .await?;
+
+    Ok(LatestBlockhash {
+        hash,
+        last_valid_block_height,
+    })
+}
+
+/// Fetches the latest blockhash and spawns a background task that refreshes it
+/// every `interval`. Abort the returned handle to stop refreshing.
+pub async fn spawn_blockhash_refresher(
+    client: Arc<RpcClient>,
+    interval: Duration,
+) -> Result<(BlockhashCache, JoinHandle<()>)> {
+    let initial = fetch_latest(&client).await?;
+
+    let cache = BlockhashCache {
+        latest: Arc::new(RwLock::new(initial)),
+        failures: Arc::new(Mutex::new(RefreshFailures::default())),
+    };
+
+    let refreshed = cache.clone();
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            // Keep serving the previous blockhash if a refresh fails; it stays
+            // valid for a while and the next refresh will likely succeed.
+            match fetch_latest(&client).await {
+                Ok(latest) => *refreshed.latest.write().unwrap() = latest,
+                Err(e) => {
+                    let mut failures = refreshed.failures.lock().unwrap();
+                    failures.count += 1;
+                    failures.last_error = Some(e.to_string());
+                }
+            }
+        }
+    });
+
+    Ok((cache, handle))
+}
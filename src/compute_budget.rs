@@ -0,0 +1,147 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient as AsyncRpcClient, rpc_client::RpcClient,
+    rpc_response::RpcPrioritizationFee,
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+
+/// `getRecentPrioritizationFees` accepts at most this many accounts.
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriorityFee {
+    /// Fixed price in micro-lamports per compute unit.
+    Fixed(u64),
+    /// Derived from recent prioritization fees paid for the accounts involved.
+    Auto,
+}
+
+impl FromStr for PriorityFee {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(PriorityFee::Auto);
+        }
+
+        s.parse::<u64>()
+            .map(PriorityFee::Fixed)
+            .map_err(|_| format!("Invalid priority fee '{s}'. Must be 'auto' or micro-lamports"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ComputeBudget {
+    pub priority_fee: Option<PriorityFee>,
+    pub compute_units: Option<u32>,
+}
+
+impl ComputeBudget {
+    fn is_empty(&self) -> bool {
+        self.priority_fee.is_none() && self.compute_units.is_none()
+    }
+
+    fn build(&self, unit_price: Option<u64>, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut budgeted = Vec::with_capacity(instructions.len() + 2);
+
+        if let Some(units) = self.compute_units {
+            budgeted.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+        if let Some(price) = unit_price {
+            budgeted.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+
+        budgeted.extend(instructions);
+        budgeted
+    }
+
+    /// Prepends the compute budget instructions, for transactions built without
+    /// an RPC connection. Fails for an `auto` priority fee.
+    pub fn apply_offline(&self, instructions: Vec<Instruction>) -> Result<Vec<Instruction>> {
+        let unit_price = match self.priority_fee {
+            Some(PriorityFee::Fixed(price)) => Some(price),
+            Some(PriorityFee::Auto) => bail!("An auto priority fee requires an RPC connection"),
+            None => None,
+        };
+
+        Ok(self.build(unit_price, instructions))
+    }
+
+    /// Prepends the compute budget instructions, resolving an `auto` priority fee
+    /// from the recent fees paid for the instructions' writable accounts.
+    pub fn apply(
+        &self,
+        client: &RpcClient,
+        instructions: Vec<Instruction>,
+    ) -> Result<Vec<Instruction>> {
+        if self.is_empty() {
+            return Ok(instructions);
+        }
+
+        let unit_price = match self.priority_fee {
+            Some(PriorityFee::Fixed(price)) => Some(price),
+            Some(PriorityFee::Auto) => {
+                let fees = client.get_recent_prioritization_fees(&fee_accounts(&instructions))?;
+                Some(median_fee(fees))
+            }
+            None => None,
+        };
+
+        Ok(self.build(unit_price, instructions))
+    }
+
+    pub async fn apply_async(
+        &self,
+        client: &AsyncRpcClient,
+        instructions: Vec<Instruction>,
+    ) -> Result<Vec<Instruction>> {
+        if self.is_empty() {
+            return Ok(instructions);
+        }
+
+        let unit_price = match self.priority_fee {
+            Some(PriorityFee::Fixed(price)) => Some(price),
+            Some(PriorityFee::Auto) => {
+                let fees = client
+                    .get_recent_prioritization_fees(&fee_accounts(&instructions))
+                    .await?;
+                Some(median_fee(fees))
+            }
+            None => None,
+        };
+
+        Ok(self.build(unit_price, instructions))
+    }
+}
+
+fn fee_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = Vec::new();
+
+    for meta in instructions.iter().flat_map(|ix| ix.accounts.iter()) {
+        if meta.is_writable && !accounts.contains(&meta.pubkey) {
+            accounts.push(meta.pubkey);
+        }
+    }
+
+    accounts.truncate(MAX_FEE_ACCOUNTS);
+    accounts
+}
+
+/// Median of the non-zero fees paid in recent slots, or zero if nobody paid one.
+fn median_fee(fees: Vec<RpcPrioritizationFee>) -> u64 {
+    let mut fees: Vec<u64> = fees
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .filter(|fee| *fee > 0)
+        .collect();
+
+    if fees.is_empty() {
+        return 0;
+    }
+
+    fees.sort_unstable();
+    fees[fees.len() / 2]
+}
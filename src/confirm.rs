@@ -0,0 +1,179 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use futures::{stream, StreamExt};
+use solana_client::{
+    client_error::ClientError, nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcSendTransactionConfig,
+};
+use solana_sdk::{signature::Signature, transaction::Transaction};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+/// Maximum number of signatures `getSignatureStatuses` accepts per request.
+pub const MAX_SIGNATURE_STATUSES: usize = 256;
+
+pub const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+struct PendingTransaction {
+    signature: Signature,
+    transaction: Transaction,
+    last_valid_block_height: u64,
+    done: oneshot::Sender<Result<()>>,
+}
+
+/// Tracks sent transactions and confirms them in batches, rebroadcasting
+/// unconfirmed ones until their blockhash expires.
+#[derive(Clone)]
+pub struct ConfirmationQueue {
+    sender: mpsc::UnboundedSender<PendingTransaction>,
+}
+
+impl ConfirmationQueue {
+    /// Spawns the background task that polls for confirmations, rebroadcasting
+    /// up to `max_concurrent` transactions at once. It exits once every queue
+    /// handle has been dropped and all pending transactions resolved.
+    pub fn spawn(
+        client: Arc<RpcClient>,
+        poll_interval: Duration,
+        max_concurrent: usize,
+    ) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(run(client, receiver, poll_interval, max_concurrent));
+
+        (Self { sender }, handle)
+    }
+
+    /// Queues an already sent transaction and waits until it is confirmed, fails
+    /// or expires.
+    pub async fn confirm(
+        &self,
+        signature: Signature,
+        transaction: Transaction,
+        last_valid_block_height: u64,
+    ) -> Result<()> {
+        let (done, result) = oneshot::channel();
+
+        self.sender
+            .send(PendingTransaction {
+                signature,
+                transaction,
+                last_valid_block_height,
+                done,
+            })
+            .map_err(|_| anyhow!("Confirmation queue has shut down"))?;
+
+        result
+            .await
+            .map_err(|_| anyhow!("Confirmation queue dropped transaction {signature}"))?
+    }
+}
+
+async fn run(
+    client: Arc<RpcClient>,
+    mut receiver: mpsc::UnboundedReceiver<PendingTransaction>,
+    poll_interval: Duration,
+    max_concurrent: usize,
+) {
+    let mut pending: Vec<PendingTransaction> = Vec::new();
+    let mut closed = false;
+
+    loop {
+        // Wait for work when idle instead of polling an empty queue.
+        if pending.is_empty() {
+            match receiver.recv().await {
+                Some(tx) => pending.push(tx),
+                None => return,
+            }
+        }
+
+        tokio::time::sleep(poll_interval).await;
+
+        while !closed {
+            match receiver.try_recv() {
+                Ok(tx) => pending.push(tx),
+                Err(mpsc::error::TryRecvError::Empty) => break,
+                Err(mpsc::error::TryRecvError::Disconnected) => closed = true,
+            }
+        }
+
+        pending = poll(&client, pending, max_concurrent).await;
+
+        if closed && pending.is_empty() {
+            return;
+        }
+    }
+}
+
+/// Checks the status of every pending transaction, resolving the ones that
+/// landed or expired and returning the ones still in flight.
+async fn poll(
+    client: &RpcClient,
+    pending: Vec<PendingTransaction>,
+    max_concurrent: usize,
+) -> Vec<PendingTransaction> {
+    // Without the current block height we can't tell what expired, so try again
+    // next round.
+    let block_height = match client.get_block_height().await {
+        Ok(height) => height,
+        Err(_) => return pending,
+    };
+
+    let mut still_pending = Vec::new();
+    let mut unconfirmed = Vec::new();
+    let mut pending = pending.into_iter().peekable();
+
+    while pending.peek().is_some() {
+        let batch: Vec<PendingTransaction> =
+            pending.by_ref().take(MAX_SIGNATURE_STATUSES).collect();
+        let signatures: Vec<Signature> = batch.iter().map(|tx| tx.signature).collect();
+
+        let statuses = match client.get_signature_statuses(&signatures).await {
+            Ok(response) => response.value,
+            Err(_) => {
+                still_pending.extend(batch);
+                continue;
+            }
+        };
+
+        for (tx, status) in batch.into_iter().zip(statuses) {
+            match status {
+                Some(status) => {
+                    if let Some(err) = status.err.clone() {
+                        let _ = tx.done.send(Err(ClientError::from(err).into()));
+                    } else if status.satisfies_commitment(client.commitment()) {
+                        let _ = tx.done.send(Ok(()));
+                    } else {
+                        // Landed but not yet at the desired commitment.
+                        still_pending.push(tx);
+                    }
+                }
+                None if block_height > tx.last_valid_block_height => {
+                    let _ = tx.done.send(Err(anyhow!(
+                        "Transaction {} expired: block height exceeded",
+                        tx.signature
+                    )));
+                }
+                None => unconfirmed.push(tx),
+            }
+        }
+    }
+
+    // Best effort; the next poll tells us whether they landed.
+    stream::iter(unconfirmed.iter().map(|tx| &tx.transaction))
+        .for_each_concurrent(max_concurrent, |transaction| async move {
+            let config = RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..Default::default()
+            };
+            let _ = client
+                .send_transaction_with_config(transaction, config)
+                .await;
+        })
+        .await;
+    still_pending.extend(unconfirmed);
+
+    still_pending
+}
@@ -0,0 +1,123 @@
+use chrono::{TimeZone, Utc};
+use console::style;
+use mpl_migration_validator::state::{MigrationState, UnlockMethod};
+use solana_program::pubkey::Pubkey;
+
+use crate::say;
+
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => format!("invalid timestamp {timestamp}"),
+    }
+}
+
+/// Formats a duration in seconds as e.g. `2d 03h 14m 05s`.
+pub fn format_countdown(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    let (days, rest) = (seconds / 86_400, seconds % 86_400);
+    let (hours, rest) = (rest / 3_600, rest % 3_600);
+    let (minutes, seconds) = (rest / 60, rest % 60);
+
+    if days > 0 {
+        format!("{days}d {hours:02}h {minutes:02}m {seconds:02}s")
+    } else {
+        format!("{hours:02}h {minutes:02}m {seconds:02}s")
+    }
+}
+
+/// Renders `done` out of `total` as a fixed width text bar.
+pub fn render_progress(done: u32, total: u32) -> String {
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        (f64::from(done) / f64::from(total)).min(1.0)
+    };
+    let filled = (ratio * PROGRESS_BAR_WIDTH as f64).round() as usize;
+
+    format!(
+        "[{}{}] {done}/{total} ({:.1}%)",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        ratio * 100.0
+    )
+}
+
+/// Prints a migration state, counting down to the unlock time from
+/// `cluster_time`, the cluster's current unix timestamp.
+pub fn print_migration_state(state: &MigrationState, address: &Pubkey, cluster_time: i64) {
+    let info = &state.collection_info;
+    let status = &state.status;
+
+    say!(
+        "{} {}",
+        style("Migration state").bold(),
+        style(address).cyan()
+    );
+    say!("  Collection mint:  {}", info.mint);
+    say!("  Authority:        {}", info.authority);
+    say!("  Rule set:         {}", info.rule_set);
+    say!("  Collection size:  {}", info.size);
+    say!("  Unlock method:    {:?}", state.unlock_method);
+
+    if let UnlockMethod::Timed = state.unlock_method {
+        let remaining = status.unlock_time - cluster_time;
+        let countdown = if remaining > 0 {
+            style(format!("unlocks in {}", format_countdown(remaining))).yellow()
+        } else {
+            style("unlocked".to_string()).green()
+        };
+
+        say!(
+            "  Unlock time:      {} ({})",
+            format_timestamp(status.unlock_time),
+            countdown
+        );
+    }
+
+    let locked = if status.is_locked {
+        style("locked").yellow()
+    } else {
+        style("unlocked").green()
+    };
+    let in_progress = if status.in_progress {
+        style("in progress").green()
+    } else {
+        style("not started").dim()
+    };
+    say!("  Status:           {locked}, {in_progress}");
+    say!(
+        "  Items migrated:   {}",
+        render_progress(status.items_migrated, info.size)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn countdown_under_a_day_omits_days() {
+        assert_eq!(format_countdown(3_725), "01h 02m 05s");
+    }
+
+    #[test]
+    fn countdown_includes_days() {
+        let seconds = 2 * 86_400 + 3 * 3_600 + 14 * 60 + 5;
+
+        assert_eq!(format_countdown(seconds), "2d 03h 14m 05s");
+    }
+
+    #[test]
+    fn countdown_clamps_negative_durations() {
+        assert_eq!(format_countdown(-10), "00h 00m 00s");
+    }
+
+    #[test]
+    fn progress_handles_empty_collections() {
+        assert!(render_progress(0, 0).ends_with("0/0 (0.0%)"));
+        assert!(render_progress(5, 10).contains(&"#".repeat(PROGRESS_BAR_WIDTH / 2)));
+    }
+}
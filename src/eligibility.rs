@@ -0,0 +1,75 @@
+use mpl_token_metadata::state::{Metadata, TokenStandard};
+use solana_program::pubkey::Pubkey;
+use spl_token::state::Account as TokenAccount;
+use thiserror::Error;
+
+use crate::errors::TokenAccountError;
+
+/// A reason the migration program would reject an item.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Ineligibility {
+    #[error("Item has no collection")]
+    MissingCollection,
+    #[error("Collection {0} is not verified")]
+    UnverifiedCollection(Pubkey),
+    #[error("Item belongs to collection {0}, not the one being migrated")]
+    WrongCollection(Pubkey),
+    #[error("Update authority {0} does not match the migration authority")]
+    WrongUpdateAuthority(Pubkey),
+    #[error("Token standard {0:?} is not NonFungible")]
+    WrongTokenStandard(TokenStandard),
+    #[error(transparent)]
+    TokenAccount(#[from] TokenAccountError),
+    #[error("Token account is frozen")]
+    Frozen,
+    #[error("Token account has a delegate")]
+    Delegated,
+}
+
+/// Checks an item's metadata against the collection being migrated and the
+/// authority recorded in its migration state.
+pub fn check_metadata(
+    metadata: &Metadata,
+    collection_mint: &Pubkey,
+    authority: &Pubkey,
+) -> Vec<Ineligibility> {
+    let mut reasons = Vec::new();
+
+    match &metadata.collection {
+        None => reasons.push(Ineligibility::MissingCollection),
+        Some(collection) if collection.key != *collection_mint => {
+            reasons.push(Ineligibility::WrongCollection(collection.key))
+        }
+        Some(collection) if !collection.verified => {
+            reasons.push(Ineligibility::UnverifiedCollection(collection.key))
+        }
+        Some(_) => {}
+    }
+
+    if metadata.update_authority != *authority {
+        reasons.push(Ineligibility::WrongUpdateAuthority(
+            metadata.update_authority,
+        ));
+    }
+
+    // Legacy items without a token standard are treated as NonFungible.
+    match metadata.token_standard {
+        None | Some(TokenStandard::NonFungible) => {}
+        Some(standard) => reasons.push(Ineligibility::WrongTokenStandard(standard)),
+    }
+
+    reasons
+}
+
+pub fn check_token_account(token_account: &TokenAccount) -> Vec<Ineligibility> {
+    let mut reasons = Vec::new();
+
+    if token_account.is_frozen() {
+        reasons.push(Ineligibility::Frozen);
+    }
+    if token_account.delegate.is_some() {
+        reasons.push(Ineligibility::Delegated);
+    }
+
+    reasons
+}
@@ -1,3 +1,4 @@
+use solana_program::pubkey::Pubkey;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,3 +8,16 @@ pub enum CliError {
     #[error("No Solana CLI config file found.")]
     MissingSolanaConfig,
 }
+
+/// Why a mint has no single token account to migrate.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TokenAccountError {
+    #[error("Mint {0} does not exist")]
+    MissingMint(Pubkey),
+    #[error("Mint {0} has been burned")]
+    Burned(Pubkey),
+    #[error("Mint {0} has supply but no token account holding it, it may have been closed")]
+    Closed(Pubkey),
+    #[error("Mint {0} had {1} token accounts with 1 token")]
+    MultipleHolders(Pubkey, usize),
+}
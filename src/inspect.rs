@@ -0,0 +1,401 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use bincode::Options;
+use borsh::BorshDeserialize;
+use mpl_migration_validator::instruction::MigrationInstruction;
+use serde::Serialize;
+use solana_program::{
+    message::Message, pubkey::Pubkey, sanitize::Sanitize, system_instruction::SystemInstruction,
+    system_program,
+};
+use solana_sdk::{
+    compute_budget::{self, ComputeBudgetInstruction},
+    signature::Signature,
+    transaction::Transaction,
+};
+
+use crate::{
+    squads::{decode_vault_transaction_create, SQUADS_PROGRAM_ID},
+    utils::{find_metadata_pda, find_migrate_state_pda},
+};
+
+/// A decoded message, along with the signatures when it came as a transaction.
+pub struct Inspected {
+    pub message: Message,
+    pub signatures: Option<Vec<Signature>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountSummary {
+    pub index: usize,
+    pub pubkey: String,
+    pub signer: bool,
+    pub writable: bool,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstructionSummary {
+    pub program: String,
+    pub program_name: Option<&'static str>,
+    pub accounts: Vec<usize>,
+    /// Debug representation of the decoded instruction, if the program is known.
+    pub decoded: Option<String>,
+    /// Raw instruction data as bs58.
+    pub data: String,
+}
+
+// Solana serializes with fixed-width integers; rejecting trailing bytes keeps a
+// message from being mistaken for a transaction and vice versa.
+fn strict_bincode() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+}
+
+fn parse_bytes(bytes: &[u8]) -> Option<Inspected> {
+    if let Ok(transaction) = strict_bincode().deserialize::<Transaction>(bytes) {
+        let header = transaction.message.header;
+        if transaction.signatures.len() == header.num_required_signatures as usize
+            && transaction.message.sanitize().is_ok()
+        {
+            return Some(Inspected {
+                message: transaction.message,
+                signatures: Some(transaction.signatures),
+            });
+        }
+    }
+
+    match strict_bincode().deserialize::<Message>(bytes) {
+        Ok(message) if message.sanitize().is_ok() => Some(Inspected {
+            message,
+            signatures: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Decodes a bs58 or base64 serialized transaction or message, given directly
+/// or as the path of a file holding it.
+pub fn decode_serialized(input: &str) -> Result<Inspected> {
+    let path = Path::new(input);
+    let encoded = if path.is_file() {
+        fs::read_to_string(path)?
+    } else {
+        input.to_string()
+    };
+    let encoded = encoded.trim();
+
+    // Some base64 strings are valid bs58 too, so try both decodings.
+    let candidates = [
+        bs58::decode(encoded).into_vec().ok(),
+        base64::decode(encoded).ok(),
+    ];
+
+    candidates
+        .iter()
+        .flatten()
+        .find_map(|bytes| parse_bytes(bytes))
+        .ok_or_else(|| anyhow!("Input is not a bs58 or base64 serialized transaction or message"))
+}
+
+pub fn program_name(program_id: &Pubkey) -> Option<&'static str> {
+    let name = if *program_id == mpl_migration_validator::ID {
+        "Migration Validator"
+    } else if *program_id == system_program::id() {
+        "System Program"
+    } else if *program_id == compute_budget::id() {
+        "Compute Budget"
+    } else if *program_id == SQUADS_PROGRAM_ID {
+        "Squads Multisig"
+    } else if *program_id == mpl_token_metadata::ID {
+        "Token Metadata"
+    } else if *program_id == spl_token::id() {
+        "Token Program"
+    } else {
+        return None;
+    };
+
+    Some(name)
+}
+
+fn decode_instruction(program_id: &Pubkey, data: &[u8]) -> Option<String> {
+    if *program_id == mpl_migration_validator::ID {
+        MigrationInstruction::try_from_slice(data)
+            .ok()
+            .map(|instruction| format!("{instruction:?}"))
+    } else if *program_id == system_program::id() {
+        bincode::deserialize::<SystemInstruction>(data)
+            .ok()
+            .map(|instruction| format!("{instruction:?}"))
+    } else if *program_id == compute_budget::id() {
+        ComputeBudgetInstruction::try_from_slice(data)
+            .ok()
+            .map(|instruction| format!("{instruction:?}"))
+    } else if *program_id == SQUADS_PROGRAM_ID {
+        decode_vault_transaction(data)
+    } else {
+        None
+    }
+}
+
+/// Decodes a Squads vault transaction along with the instructions it wraps, so
+/// a proposal shows what members are voting on.
+fn decode_vault_transaction(data: &[u8]) -> Option<String> {
+    let transaction = decode_vault_transaction_create(data).ok()??;
+
+    let instructions: Vec<String> = transaction
+        .instructions
+        .iter()
+        .map(|instruction| {
+            // Keys past `account_keys` come from address lookup tables.
+            let program_id = transaction
+                .account_keys
+                .get(instruction.program_id_index as usize);
+            let program = program_id.map_or("Unknown Program".to_string(), |program_id| {
+                program_name(program_id)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| program_id.to_string())
+            });
+            let decoded = program_id
+                .and_then(|program_id| decode_instruction(program_id, &instruction.data))
+                .unwrap_or_else(|| bs58::encode(&instruction.data).into_string());
+
+            format!("{program}: {decoded}")
+        })
+        .collect();
+
+    Some(format!(
+        "VaultTransactionCreate {{ vault_index: {}, instructions: [{}] }}",
+        transaction.vault_index,
+        instructions.join(", ")
+    ))
+}
+
+fn push_label(labels: &mut Vec<String>, label: &str) {
+    if !labels.iter().any(|l| l == label) {
+        labels.push(label.to_string());
+    }
+}
+
+/// Labels the accounts of a message with the roles they play in a migration.
+fn label_accounts(message: &Message) -> Vec<Vec<String>> {
+    let keys = &message.account_keys;
+    let position = |pubkey: &Pubkey| keys.iter().position(|key| key == pubkey);
+
+    let mut labels = vec![Vec::new(); keys.len()];
+    if let Some(payer) = labels.first_mut() {
+        push_label(payer, "fee payer");
+    }
+
+    for (i, key) in keys.iter().enumerate() {
+        if let Some(name) = program_name(key) {
+            push_label(&mut labels[i], name);
+        }
+
+        // A key whose migration state PDA is also present is the collection mint.
+        if let Some(state) = position(&find_migrate_state_pda(key).0) {
+            push_label(&mut labels[i], "collection mint");
+            push_label(&mut labels[state], "migration state");
+
+            if let Some(metadata) = position(&find_metadata_pda(key).0) {
+                push_label(&mut labels[metadata], "collection metadata");
+            }
+        }
+    }
+
+    for instruction in &message.instructions {
+        let program_id = &keys[instruction.program_id_index as usize];
+
+        if *program_id == mpl_migration_validator::ID {
+            // Signers other than the fee payer sign as the authority; if there
+            // are none, the fee payer is the authority as well.
+            let signers: Vec<usize> = instruction
+                .accounts
+                .iter()
+                .map(|&index| index as usize)
+                .filter(|&index| message.is_signer(index))
+                .collect();
+
+            match signers.iter().find(|&&index| index != 0) {
+                Some(&authority) => push_label(&mut labels[authority], "authority"),
+                None if signers.contains(&0) => push_label(&mut labels[0], "authority"),
+                None => {}
+            }
+        } else if *program_id == system_program::id() {
+            if let Ok(SystemInstruction::AdvanceNonceAccount) =
+                bincode::deserialize::<SystemInstruction>(&instruction.data)
+            {
+                if let Some(&nonce) = instruction.accounts.first() {
+                    push_label(&mut labels[nonce as usize], "nonce account");
+                }
+            }
+        }
+    }
+
+    labels
+}
+
+pub fn summarize_accounts(message: &Message) -> Vec<AccountSummary> {
+    message
+        .account_keys
+        .iter()
+        .zip(label_accounts(message))
+        .enumerate()
+        .map(|(index, (pubkey, labels))| AccountSummary {
+            index,
+            pubkey: pubkey.to_string(),
+            signer: message.is_signer(index),
+            writable: message.is_writable(index),
+            labels,
+        })
+        .collect()
+}
+
+pub fn summarize_instructions(message: &Message) -> Vec<InstructionSummary> {
+    message
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let program_id = &message.account_keys[instruction.program_id_index as usize];
+
+            InstructionSummary {
+                program: program_id.to_string(),
+                program_name: program_name(program_id),
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|&index| index as usize)
+                    .collect(),
+                decoded: decode_instruction(program_id, &instruction.data),
+                data: bs58::encode(&instruction.data).into_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use mpl_migration_validator::{
+        instruction::{initialize, InitializeArgs},
+        state::UnlockMethod,
+    };
+
+    use super::*;
+    use crate::{
+        compute_budget::ComputeBudget,
+        methods::{initialize_msg, update_msg, InitializeMsgParams, UpdateMsgParams},
+        squads::SquadsProposal,
+    };
+
+    fn decoded(inspected: &Inspected) -> Vec<String> {
+        summarize_instructions(&inspected.message)
+            .into_iter()
+            .flat_map(|summary| summary.decoded)
+            .collect()
+    }
+
+    #[test]
+    fn decodes_initialize_messages() {
+        let collection_mint = Pubkey::new_unique();
+        let message = initialize_msg(InitializeMsgParams {
+            payer: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            rule_set: None,
+            collection_mint,
+            unlock_method: UnlockMethod::Vote,
+            collection_size: 42,
+            compute_budget: ComputeBudget::default(),
+            nonce: None,
+        })
+        .unwrap();
+
+        let inspected = decode_serialized(&message).unwrap();
+
+        assert!(inspected.signatures.is_none());
+        assert_eq!(
+            inspected.message.serialize(),
+            bs58::decode(&message).into_vec().unwrap()
+        );
+        let decoded = decoded(&inspected);
+        assert!(decoded[0].contains("Initialize(InitializeArgs"));
+        assert!(decoded[0].contains("collection_size: 42"));
+
+        let labels = summarize_accounts(&inspected.message);
+        let mint = labels
+            .iter()
+            .find(|account| account.pubkey == collection_mint.to_string())
+            .unwrap();
+        assert!(mint.labels.contains(&"collection mint".to_string()));
+    }
+
+    #[test]
+    fn decodes_update_messages_as_base64_transactions() {
+        let message = update_msg(UpdateMsgParams {
+            payer: Pubkey::new_unique(),
+            authority_pubkey: Pubkey::new_unique(),
+            migration_state: Pubkey::new_unique(),
+            rule_set: None,
+            collection_size: Some(7),
+            new_update_authority: None,
+            compute_budget: ComputeBudget::default(),
+            nonce: None,
+        })
+        .unwrap();
+        let message: Message =
+            bincode::deserialize(&bs58::decode(&message).into_vec().unwrap()).unwrap();
+
+        let signatures =
+            vec![Signature::default(); message.header.num_required_signatures as usize];
+        let transaction = Transaction {
+            signatures: signatures.clone(),
+            message: message.clone(),
+        };
+        let bytes = bincode::serialize(&transaction).unwrap();
+
+        let inspected = parse_bytes(&bytes).unwrap();
+        assert_eq!(inspected.message, message);
+        assert_eq!(inspected.signatures, Some(signatures));
+
+        let inspected = decode_serialized(&base64::encode(&bytes)).unwrap();
+        let decoded = decoded(&inspected);
+        assert!(decoded[0].contains("Update(UpdateArgs"));
+        assert!(decoded[0].contains("collection_size: Some(7)"));
+    }
+
+    #[test]
+    fn decodes_instructions_wrapped_in_proposals() {
+        let proposal = SquadsProposal {
+            multisig: Pubkey::new_unique(),
+            vault_index: 0,
+            transaction_index: 1,
+        };
+        let creator = Pubkey::new_unique();
+        let instruction = initialize(
+            creator,
+            proposal.vault(),
+            Pubkey::new_unique(),
+            InitializeArgs {
+                rule_set: None,
+                unlock_method: UnlockMethod::Timed,
+                collection_size: 3,
+            },
+        );
+        let instructions = proposal.wrap(&creator, &creator, &[instruction]).unwrap();
+        let message = Message::new(&instructions, Some(&creator));
+
+        let inspected = parse_bytes(&message.serialize()).unwrap();
+        let decoded = decoded(&inspected);
+
+        assert!(decoded[0].starts_with("VaultTransactionCreate { vault_index: 0"));
+        assert!(decoded[0].contains("Migration Validator: Initialize(InitializeArgs"));
+        assert!(decoded[0].contains("collection_size: 3"));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_bytes(&[1, 2, 3]).is_none());
+        assert!(decode_serialized("not a message").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    processor::{IneligibleMint, MigratedMint, MigrationError},
+    say,
+};
+
+/// A single line of the migration journal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JournalEntry {
+    Migrated(MigratedMint),
+    AlreadyMigrated { item_mint: String },
+    Ineligible(IneligibleMint),
+    Failed(MigrationError),
+}
+
+/// Append-only, newline-delimited JSON log of every migration result, written
+/// as each result comes in so an interrupted run can be resumed.
+pub struct Journal {
+    file: File,
+    /// Set when the journal ends in a line cut off by a crash, so the next
+    /// entry starts on a line of its own.
+    needs_newline: bool,
+}
+
+pub fn journal_path(collection_mint: &Pubkey) -> PathBuf {
+    PathBuf::from(format!("{collection_mint}_migration_journal.jsonl"))
+}
+
+impl Journal {
+    /// Opens the journal for writing. When resuming, new entries are appended
+    /// to the existing journal; otherwise it must not hold any entries yet, so
+    /// a previous run's results are never thrown away.
+    pub fn open(path: &Path, resume: bool) -> Result<Self> {
+        let len = match fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+
+        if len > 0 && !resume {
+            bail!(
+                "Journal {} already has entries from a previous run, pass --resume to continue it or move it away to start over",
+                path.display()
+            );
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        let needs_newline = if len > 0 {
+            let mut last = [0; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            last[0] != b'\n'
+        } else {
+            false
+        };
+
+        Ok(Self {
+            file,
+            needs_newline,
+        })
+    }
+
+    pub fn record(&mut self, entry: &JournalEntry) -> Result<()> {
+        let mut line = Vec::new();
+        if self.needs_newline {
+            line.push(b'\n');
+        }
+        serde_json::to_writer(&mut line, entry)?;
+        line.push(b'\n');
+
+        // Write each entry in a single call so a crash can at worst leave one
+        // truncated line at the end of the file.
+        self.file.write_all(&line)?;
+        self.file.flush()?;
+        self.needs_newline = false;
+
+        Ok(())
+    }
+}
+
+/// Reads all entries from an existing journal. A missing journal is treated as
+/// empty and lines that fail to parse, e.g. one cut off by a crash, are skipped.
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => say!("Skipping malformed journal line: {line}"),
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn temp_journal() -> PathBuf {
+        env::temp_dir().join(journal_path(&Pubkey::new_unique()))
+    }
+
+    fn entry(item_mint: &str) -> JournalEntry {
+        JournalEntry::AlreadyMigrated {
+            item_mint: item_mint.to_string(),
+        }
+    }
+
+    #[test]
+    fn refuses_to_overwrite_without_resume() {
+        let path = temp_journal();
+        Journal::open(&path, false)
+            .unwrap()
+            .record(&entry("a"))
+            .unwrap();
+
+        assert!(Journal::open(&path, false).is_err());
+        assert_eq!(read_journal(&path).unwrap().len(), 1);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn resumes_after_a_truncated_line() {
+        let path = temp_journal();
+        fs::write(
+            &path,
+            "{\"status\":\"already_migrated\",\"item_mint\":\"a\"}\n{\"stat",
+        )
+        .unwrap();
+
+        Journal::open(&path, true)
+            .unwrap()
+            .record(&entry("b"))
+            .unwrap();
+
+        let entries = read_journal(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(
+            &entries[1],
+            JournalEntry::AlreadyMigrated { item_mint } if item_mint == "b"
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+}
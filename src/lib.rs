@@ -1,10 +1,26 @@
 use std::fmt;
 
 pub mod args;
+pub mod blockhash;
+pub mod compute_budget;
+pub mod confirm;
+pub mod display;
+pub mod eligibility;
 pub mod errors;
+pub mod inspect;
+pub mod journal;
 pub mod methods;
+pub mod mint_list;
+pub mod nonce;
+pub mod offline;
+pub mod output;
 pub mod processor;
+pub mod rate_limit;
+pub mod retry;
+pub mod rpc_pool;
+pub mod run_report;
 pub mod setup;
+pub mod squads;
 pub mod utils;
 
 pub enum Cluster {
@@ -1,9 +1,15 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use clap::Parser;
 
 use goose::{
     args::{self, Commands},
+    compute_budget::ComputeBudget,
+    output::set_output_format,
     processor::*,
+    retry::RetryPolicy,
+    setup::GlobalOptions,
 };
 
 #[tokio::main]
@@ -12,38 +18,54 @@ async fn main() -> Result<()> {
 
     let args = args::Args::parse();
 
-    let keypair_path = args.keypair_path.clone();
-    let rpc_url = args.rpc_url.clone();
+    set_output_format(args.output);
+
+    let options = GlobalOptions {
+        keypair_path: args.keypair_path.clone(),
+        fee_payer_path: args.fee_payer.clone(),
+        rpc_urls: args.rpc_url.clone(),
+        compute_budget: ComputeBudget {
+            priority_fee: args.priority_fee,
+            compute_units: args.compute_units,
+        },
+        dry_run: args.dry_run,
+        nonce_account: args.nonce_account,
+        nonce_authority: args.nonce_authority,
+        multisig: args.multisig,
+        vault_index: args.vault_index,
+    };
 
     match args.command {
         Commands::Init {
             collection_mint,
             unlock_method,
             size,
-        } => process_initialize(keypair_path, rpc_url, collection_mint, unlock_method, size),
+        } => process_initialize(&options, collection_mint, unlock_method, size),
         Commands::InitMsg {
             payer,
             authority,
             collection_mint,
             unlock_method,
             size,
-        } => process_initialize_msg(payer, authority, collection_mint, unlock_method, size),
-        Commands::InitSigner => process_initialize_signer(keypair_path, rpc_url),
-        Commands::Cancel { collection_mint } => {
-            process_close(keypair_path, rpc_url, collection_mint)
-        }
-        Commands::GetState { collection_mint } => {
-            process_get_state(keypair_path, rpc_url, collection_mint)
-        }
-        Commands::GetAllStates => process_get_all_states(keypair_path, rpc_url),
+        } => process_initialize_msg(
+            &options,
+            payer,
+            authority,
+            collection_mint,
+            unlock_method,
+            size,
+        ),
+        Commands::InitSigner => process_initialize_signer(&options),
+        Commands::Cancel { collection_mint } => process_close(&options, collection_mint),
+        Commands::GetState { collection_mint } => process_get_state(&options, collection_mint),
+        Commands::GetAllStates => process_get_all_states(&options),
         Commands::Update {
             collection_mint,
             rule_set,
             size,
             new_update_authority,
         } => process_update(
-            keypair_path,
-            rpc_url,
+            &options,
             collection_mint,
             rule_set,
             size,
@@ -56,34 +78,136 @@ async fn main() -> Result<()> {
             new_update_authority,
             authority_pubkey,
         } => process_update_msg(
-            keypair_path,
-            rpc_url,
+            &options,
             collection_mint,
             rule_set,
             size,
             new_update_authority,
             authority_pubkey,
         ),
-        Commands::Start { collection_mint } => {
-            process_start(keypair_path, rpc_url, collection_mint)
-        }
+        Commands::Start { collection_mint } => process_start(&options, collection_mint),
         Commands::Migrate {
             collection_mint,
             mint_list,
+            mint_column,
+            skip_invalid,
             batch_size,
+            resume,
+            max_attempts,
+            retry_delay_ms,
+            send_mode,
+            max_rps,
+            wait_for_unlock,
         } => {
+            let retry_policy = RetryPolicy {
+                max_attempts,
+                base_delay: Duration::from_millis(retry_delay_ms),
+                ..Default::default()
+            };
+
             process_migrate(
-                keypair_path,
-                rpc_url,
+                &options,
                 collection_mint,
                 mint_list,
+                mint_column,
+                skip_invalid,
                 batch_size,
+                resume,
+                retry_policy,
+                send_mode,
+                max_rps,
+                wait_for_unlock,
             )
             .await
         }
+        Commands::ValidateList {
+            mint_list,
+            mint_column,
+            output_file,
+        } => process_validate_list(mint_list, mint_column, output_file),
+        Commands::Snapshot {
+            collection_mint,
+            first_creator,
+            output_file,
+        } => process_snapshot(&options, collection_mint, first_creator, output_file),
         Commands::Check {
             mint_list,
+            mint_column,
+            skip_invalid,
+            batch_size,
+            max_rps,
+        } => {
+            process_check(
+                &options,
+                mint_list,
+                mint_column,
+                skip_invalid,
+                batch_size,
+                max_rps,
+            )
+            .await
+        }
+        Commands::Run {
+            collection_mint,
+            mint_list,
+            mint_column,
+            skip_invalid,
+            unlock_method,
+            size,
             batch_size,
-        } => process_check(keypair_path, rpc_url, mint_list, batch_size).await,
+            max_attempts,
+            retry_delay_ms,
+            send_mode,
+            max_rps,
+        } => {
+            let retry_policy = RetryPolicy {
+                max_attempts,
+                base_delay: Duration::from_millis(retry_delay_ms),
+                ..Default::default()
+            };
+
+            process_run(
+                &options,
+                collection_mint,
+                mint_list,
+                mint_column,
+                skip_invalid,
+                unlock_method,
+                size,
+                batch_size,
+                retry_policy,
+                send_mode,
+                max_rps,
+            )
+            .await
+        }
+        Commands::SignMsg {
+            message,
+            blockhash,
+            output_file,
+        } => process_sign_msg(&options, message, blockhash, output_file),
+        Commands::Submit { signature_files } => process_submit(&options, signature_files),
+        Commands::Inspect { input } => process_inspect(input),
+        Commands::CreateNonce {
+            nonce_keypair,
+            authority,
+        } => process_create_nonce(&options, nonce_keypair, authority),
+        Commands::Owners {
+            mint_list,
+            mint_column,
+            skip_invalid,
+            batch_size,
+            output_file,
+        } => {
+            process_owners(
+                &options,
+                mint_list,
+                mint_column,
+                skip_invalid,
+                batch_size,
+                output_file,
+            )
+            .await
+        }
     }
 }
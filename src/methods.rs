@@ -4,14 +4,128 @@ use mpl_migration_validator::{
     instruction::{InitializeArgs, UpdateArgs},
     state::{MigrationState, UnlockMethod},
 };
-use solana_client::rpc_client::RpcClient;
-use solana_program::{message::Message, pubkey::Pubkey};
+use mpl_token_metadata::state::{Metadata, TokenMetadataAccount};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient as AsyncRpcClient,
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey};
 use solana_sdk::{
+    hash::Hash,
     signature::{Keypair, Signature, Signer},
     transaction::Transaction,
 };
 
-use crate::utils::find_migrate_state_pda;
+use crate::{
+    compute_budget::ComputeBudget,
+    nonce::{create_nonce_account_instructions, DurableNonce},
+    squads::SquadsProposal,
+    utils::{find_metadata_pda, find_migrate_state_pda},
+};
+
+/// Uses the provided blockhash, e.g. one from a shared `BlockhashCache`, or
+/// fetches the latest one.
+fn blockhash_or_latest(client: &RpcClient, recent_blockhash: Option<Hash>) -> Result<Hash> {
+    match recent_blockhash {
+        Some(hash) => Ok(hash),
+        None => Ok(client.get_latest_blockhash()?),
+    }
+}
+
+/// Advances the durable nonce and uses its stored blockhash when one is given,
+/// otherwise uses the provided or latest blockhash.
+fn with_nonce_or_blockhash(
+    client: &RpcClient,
+    instructions: Vec<Instruction>,
+    recent_blockhash: Option<Hash>,
+    nonce: Option<DurableNonce>,
+) -> Result<(Vec<Instruction>, Hash)> {
+    match nonce {
+        Some(nonce) => Ok((nonce.apply(instructions), nonce.blockhash)),
+        None => Ok((instructions, blockhash_or_latest(client, recent_blockhash)?)),
+    }
+}
+
+/// Builds a message for offline signing. Without a durable nonce the blockhash
+/// is left unset for the signers to fill in.
+fn offline_message(
+    instructions: Vec<Instruction>,
+    payer: &Pubkey,
+    nonce: Option<DurableNonce>,
+) -> Message {
+    match nonce {
+        Some(nonce) => {
+            Message::new_with_blockhash(&nonce.apply(instructions), Some(payer), &nonce.blockhash)
+        }
+        None => Message::new(&instructions, Some(payer)),
+    }
+}
+
+/// The multisig vault acts in place of the signer when a proposal is given.
+fn authority_or_vault(signer: &Keypair, multisig: Option<SquadsProposal>) -> Pubkey {
+    match multisig {
+        Some(proposal) => proposal.vault(),
+        None => signer.pubkey(),
+    }
+}
+
+/// Wraps the instruction in a vault transaction proposal when a multisig is
+/// given, otherwise sends it as is.
+fn propose_or_direct(
+    multisig: Option<SquadsProposal>,
+    creator: &Keypair,
+    rent_payer: &Keypair,
+    instruction: Instruction,
+) -> Result<Vec<Instruction>> {
+    match multisig {
+        Some(proposal) => proposal.wrap(&creator.pubkey(), &rent_payer.pubkey(), &[instruction]),
+        None => Ok(vec![instruction]),
+    }
+}
+
+/// Result of a simulated transaction.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Simulation {
+    pub err: Option<String>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+impl From<RpcSimulateTransactionResult> for Simulation {
+    fn from(result: RpcSimulateTransactionResult) -> Self {
+        Self {
+            err: result.err.map(|e| e.to_string()),
+            units_consumed: result.units_consumed,
+            logs: result.logs.unwrap_or_default(),
+        }
+    }
+}
+
+pub enum TxOutcome {
+    Sent(Signature),
+    Simulated(Simulation),
+}
+
+/// Sends and confirms the transaction, or only simulates it for a dry run.
+pub fn send_or_simulate(
+    client: &RpcClient,
+    transaction: &Transaction,
+    dry_run: bool,
+) -> Result<TxOutcome> {
+    if dry_run {
+        let result = client.simulate_transaction(transaction)?.value;
+        return Ok(TxOutcome::Simulated(result.into()));
+    }
+
+    let sig = client.send_and_confirm_transaction(transaction)?;
+
+    Ok(TxOutcome::Sent(sig))
+}
 
 pub struct InitializeParams<'a> {
     pub client: &'a RpcClient,
@@ -21,9 +135,14 @@ pub struct InitializeParams<'a> {
     pub collection_mint: Pubkey,
     pub unlock_method: UnlockMethod,
     pub collection_size: u32,
+    pub recent_blockhash: Option<Hash>,
+    pub compute_budget: ComputeBudget,
+    pub nonce: Option<DurableNonce>,
+    pub multisig: Option<SquadsProposal>,
+    pub dry_run: bool,
 }
 
-pub fn initialize(params: InitializeParams) -> Result<Signature> {
+pub fn initialize(params: InitializeParams) -> Result<TxOutcome> {
     let InitializeParams {
         client,
         payer,
@@ -32,6 +151,11 @@ pub fn initialize(params: InitializeParams) -> Result<Signature> {
         collection_mint,
         unlock_method,
         collection_size,
+        recent_blockhash,
+        compute_budget,
+        nonce,
+        multisig,
+        dry_run,
     } = params;
 
     let args = InitializeArgs {
@@ -41,24 +165,26 @@ pub fn initialize(params: InitializeParams) -> Result<Signature> {
     };
 
     let instruction = mpl_migration_validator::instruction::initialize(
-        payer.pubkey(),
-        authority.pubkey(),
+        authority_or_vault(payer, multisig),
+        authority_or_vault(authority, multisig),
         collection_mint,
         args,
     );
 
-    let recent_blockhash = client.get_latest_blockhash()?;
+    let instructions = propose_or_direct(multisig, authority, payer, instruction)?;
+    let instructions = compute_budget.apply(client, instructions)?;
+
+    let (instructions, recent_blockhash) =
+        with_nonce_or_blockhash(client, instructions, recent_blockhash, nonce)?;
 
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
+        &instructions,
         Some(&payer.pubkey()),
         &[payer, authority],
         recent_blockhash,
     );
 
-    let sig = client.send_and_confirm_transaction(&transaction)?;
-
-    Ok(sig)
+    send_or_simulate(client, &transaction, dry_run)
 }
 
 pub struct InitializeMsgParams {
@@ -68,6 +194,8 @@ pub struct InitializeMsgParams {
     pub collection_mint: Pubkey,
     pub unlock_method: UnlockMethod,
     pub collection_size: u32,
+    pub compute_budget: ComputeBudget,
+    pub nonce: Option<DurableNonce>,
 }
 
 pub fn initialize_msg(params: InitializeMsgParams) -> Result<String> {
@@ -78,6 +206,8 @@ pub fn initialize_msg(params: InitializeMsgParams) -> Result<String> {
         collection_mint,
         unlock_method,
         collection_size,
+        compute_budget,
+        nonce,
     } = params;
 
     let args = InitializeArgs {
@@ -89,59 +219,88 @@ pub fn initialize_msg(params: InitializeMsgParams) -> Result<String> {
     let instruction =
         mpl_migration_validator::instruction::initialize(payer, authority, collection_mint, args);
 
-    let message = Message::new(&[instruction], Some(&payer));
+    let instructions = compute_budget.apply_offline(vec![instruction])?;
+    let message = offline_message(instructions, &payer, nonce);
     Ok(bs58::encode(message.serialize()).into_string())
 }
 
 pub struct CloseParams<'a> {
     pub client: &'a RpcClient,
+    pub payer: &'a Keypair,
     pub authority: &'a Keypair,
     pub collection_mint: Pubkey,
+    pub recent_blockhash: Option<Hash>,
+    pub compute_budget: ComputeBudget,
+    pub nonce: Option<DurableNonce>,
+    pub multisig: Option<SquadsProposal>,
+    pub dry_run: bool,
 }
 
-pub fn close(params: CloseParams) -> Result<Signature> {
+pub fn close(params: CloseParams) -> Result<TxOutcome> {
     let CloseParams {
         client,
+        payer,
         authority,
         collection_mint,
+        recent_blockhash,
+        compute_budget,
+        nonce,
+        multisig,
+        dry_run,
     } = params;
 
     let migrate_state_pubkey = find_migrate_state_pda(&collection_mint).0;
 
-    let instruction =
-        mpl_migration_validator::instruction::close(authority.pubkey(), migrate_state_pubkey);
+    let instruction = mpl_migration_validator::instruction::close(
+        authority_or_vault(authority, multisig),
+        migrate_state_pubkey,
+    );
+
+    let instructions = propose_or_direct(multisig, authority, payer, instruction)?;
+    let instructions = compute_budget.apply(client, instructions)?;
 
-    let recent_blockhash = client.get_latest_blockhash()?;
+    let (instructions, recent_blockhash) =
+        with_nonce_or_blockhash(client, instructions, recent_blockhash, nonce)?;
 
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
-        Some(&authority.pubkey()),
-        &[authority],
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer, authority],
         recent_blockhash,
     );
 
-    let sig = client.send_and_confirm_transaction(&transaction)?;
-
-    Ok(sig)
+    send_or_simulate(client, &transaction, dry_run)
 }
 
 pub struct UpdateParams<'a> {
     pub client: &'a RpcClient,
+    pub payer: &'a Keypair,
     pub authority: &'a Keypair,
     pub migration_state: Pubkey,
     pub rule_set: Option<Pubkey>,
     pub collection_size: Option<u32>,
     pub new_update_authority: Option<Pubkey>,
+    pub recent_blockhash: Option<Hash>,
+    pub compute_budget: ComputeBudget,
+    pub nonce: Option<DurableNonce>,
+    pub multisig: Option<SquadsProposal>,
+    pub dry_run: bool,
 }
 
-pub fn update(params: UpdateParams) -> Result<Signature> {
+pub fn update(params: UpdateParams) -> Result<TxOutcome> {
     let UpdateParams {
         client,
+        payer,
         authority,
         migration_state,
         rule_set,
         collection_size,
         new_update_authority,
+        recent_blockhash,
+        compute_budget,
+        nonce,
+        multisig,
+        dry_run,
     } = params;
 
     let args = UpdateArgs {
@@ -150,40 +309,49 @@ pub fn update(params: UpdateParams) -> Result<Signature> {
         new_update_authority,
     };
 
-    let instruction =
-        mpl_migration_validator::instruction::update(authority.pubkey(), migration_state, args);
+    let instruction = mpl_migration_validator::instruction::update(
+        authority_or_vault(authority, multisig),
+        migration_state,
+        args,
+    );
+
+    let instructions = propose_or_direct(multisig, authority, payer, instruction)?;
+    let instructions = compute_budget.apply(client, instructions)?;
 
-    let recent_blockhash = client.get_latest_blockhash()?;
+    let (instructions, recent_blockhash) =
+        with_nonce_or_blockhash(client, instructions, recent_blockhash, nonce)?;
 
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
-        Some(&authority.pubkey()),
-        &[authority],
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer, authority],
         recent_blockhash,
     );
 
-    let sig = client.send_and_confirm_transaction(&transaction)?;
-
-    Ok(sig)
+    send_or_simulate(client, &transaction, dry_run)
 }
 
-pub struct UpdateMsgParams<'a> {
-    pub authority: &'a Keypair,
+pub struct UpdateMsgParams {
+    pub payer: Pubkey,
     pub authority_pubkey: Pubkey,
     pub migration_state: Pubkey,
     pub rule_set: Option<Pubkey>,
     pub collection_size: Option<u32>,
     pub new_update_authority: Option<Pubkey>,
+    pub compute_budget: ComputeBudget,
+    pub nonce: Option<DurableNonce>,
 }
 
 pub fn update_msg(params: UpdateMsgParams) -> Result<String> {
     let UpdateMsgParams {
-        authority,
+        payer,
         authority_pubkey,
         migration_state,
         rule_set,
         collection_size,
         new_update_authority,
+        compute_budget,
+        nonce,
     } = params;
 
     let args = UpdateArgs {
@@ -195,41 +363,98 @@ pub fn update_msg(params: UpdateMsgParams) -> Result<String> {
     let instruction =
         mpl_migration_validator::instruction::update(authority_pubkey, migration_state, args);
 
-    let message = Message::new(&[instruction], Some(&authority.pubkey()));
+    let instructions = compute_budget.apply_offline(vec![instruction])?;
+    let message = offline_message(instructions, &payer, nonce);
     Ok(bs58::encode(message.serialize()).into_string())
 }
 
 pub struct StartParams<'a> {
     pub client: &'a RpcClient,
+    pub payer: &'a Keypair,
     pub authority: &'a Keypair,
     pub collection_mint: Pubkey,
+    pub recent_blockhash: Option<Hash>,
+    pub compute_budget: ComputeBudget,
+    pub nonce: Option<DurableNonce>,
+    pub multisig: Option<SquadsProposal>,
+    pub dry_run: bool,
 }
 
-pub fn start(params: StartParams) -> Result<Signature> {
+pub fn start(params: StartParams) -> Result<TxOutcome> {
     let StartParams {
         client,
+        payer,
         authority,
         collection_mint,
+        recent_blockhash,
+        compute_budget,
+        nonce,
+        multisig,
+        dry_run,
     } = params;
 
     let instruction = mpl_migration_validator::instruction::start(
-        authority.pubkey(),
-        authority.pubkey(),
+        authority_or_vault(payer, multisig),
+        authority_or_vault(authority, multisig),
         collection_mint,
     );
 
-    let recent_blockhash = client.get_latest_blockhash()?;
+    let instructions = propose_or_direct(multisig, authority, payer, instruction)?;
+    let instructions = compute_budget.apply(client, instructions)?;
+
+    let (instructions, recent_blockhash) =
+        with_nonce_or_blockhash(client, instructions, recent_blockhash, nonce)?;
 
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
-        Some(&authority.pubkey()),
-        &[authority],
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer, authority],
         recent_blockhash,
     );
 
-    let sig = client.send_and_confirm_transaction(&transaction)?;
+    send_or_simulate(client, &transaction, dry_run)
+}
 
-    Ok(sig)
+pub struct CreateNonceParams<'a> {
+    pub client: &'a RpcClient,
+    pub payer: &'a Keypair,
+    pub nonce_account: &'a Keypair,
+    pub authority: Pubkey,
+    pub recent_blockhash: Option<Hash>,
+    pub compute_budget: ComputeBudget,
+    pub dry_run: bool,
+}
+
+pub fn create_nonce_account(params: CreateNonceParams) -> Result<TxOutcome> {
+    let CreateNonceParams {
+        client,
+        payer,
+        nonce_account,
+        authority,
+        recent_blockhash,
+        compute_budget,
+        dry_run,
+    } = params;
+
+    let instructions = create_nonce_account_instructions(
+        client,
+        &payer.pubkey(),
+        &nonce_account.pubkey(),
+        &authority,
+    )?;
+
+    let instructions = compute_budget.apply(client, instructions)?;
+
+    let recent_blockhash = blockhash_or_latest(client, recent_blockhash)?;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer, nonce_account],
+        recent_blockhash,
+    );
+
+    send_or_simulate(client, &transaction, dry_run)
 }
 
 pub struct GetStateParams<'a> {
@@ -252,8 +477,88 @@ pub fn get_state(params: GetStateParams) -> Result<MigrationState> {
     Ok(state)
 }
 
-pub struct MigrateParams<'a> {
+pub struct GetStateAsyncParams<'a> {
+    pub client: &'a AsyncRpcClient,
+    pub collection_mint: Pubkey,
+}
+
+pub async fn get_state_async(params: GetStateAsyncParams<'_>) -> Result<MigrationState> {
+    let GetStateAsyncParams {
+        client,
+        collection_mint,
+    } = params;
+
+    let pubkey = find_migrate_state_pda(&collection_mint).0;
+
+    let account = client.get_account_data(&pubkey).await?;
+
+    let state = MigrationState::deserialize(&mut account.as_slice())?;
+
+    Ok(state)
+}
+
+/// Offset of the first creator's address in a metadata account, given the
+/// fixed-size name, symbol and URI fields.
+const FIRST_CREATOR_OFFSET: usize = 326;
+
+pub struct SnapshotParams<'a> {
     pub client: &'a RpcClient,
+    pub collection_mint: Pubkey,
+    pub first_creator: Option<Pubkey>,
+}
+
+/// Finds the mints of all items with a verified collection matching `collection_mint`.
+pub fn snapshot(params: SnapshotParams) -> Result<Vec<Pubkey>> {
+    let SnapshotParams {
+        client,
+        collection_mint,
+        first_creator,
+    } = params;
+
+    let filters = first_creator.map(|creator| {
+        vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            FIRST_CREATOR_OFFSET,
+            creator.as_ref(),
+        ))]
+    });
+
+    let config = RpcProgramAccountsConfig {
+        filters,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let accounts = client.get_program_accounts_with_config(&mpl_token_metadata::ID, config)?;
+
+    let mut mints = Vec::new();
+
+    for (pubkey, account) in accounts {
+        // Editions, master editions and other token metadata accounts won't deserialize.
+        let metadata = match Metadata::safe_deserialize(&account.data) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if find_metadata_pda(&metadata.mint).0 != pubkey {
+            continue;
+        }
+
+        match metadata.collection {
+            Some(collection) if collection.verified && collection.key == collection_mint => {
+                mints.push(metadata.mint)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mints)
+}
+
+pub struct MigrateAsyncParams<'a> {
+    pub client: &'a AsyncRpcClient,
     pub payer: &'a Keypair,
     pub item_mint: Pubkey,
     pub item_token: Pubkey,
@@ -262,10 +567,23 @@ pub struct MigrateParams<'a> {
     pub token_owner_program_buffer: Option<Pubkey>,
     pub collection_mint: Pubkey,
     pub rule_set: Pubkey,
+    pub recent_blockhash: Option<Hash>,
+    pub compute_budget: ComputeBudget,
+    pub nonce: Option<DurableNonce>,
 }
 
-pub fn migrate_item(params: MigrateParams) -> Result<Signature> {
-    let MigrateParams {
+pub async fn migrate_item_async(params: MigrateAsyncParams<'_>) -> Result<Signature> {
+    let client = params.client;
+    let transaction = migrate_item_transaction(params).await?;
+
+    let sig = client.send_and_confirm_transaction(&transaction).await?;
+
+    Ok(sig)
+}
+
+/// Builds and signs a migrate item transaction without sending it.
+pub async fn migrate_item_transaction(params: MigrateAsyncParams<'_>) -> Result<Transaction> {
+    let MigrateAsyncParams {
         client,
         payer,
         item_mint,
@@ -275,6 +593,9 @@ pub fn migrate_item(params: MigrateParams) -> Result<Signature> {
         token_owner_program_buffer,
         collection_mint,
         rule_set,
+        recent_blockhash,
+        compute_budget,
+        nonce,
     } = params;
 
     let instruction = mpl_migration_validator::instruction::migrate_item(
@@ -288,16 +609,22 @@ pub fn migrate_item(params: MigrateParams) -> Result<Signature> {
         rule_set,
     );
 
-    let recent_blockhash = client.get_latest_blockhash()?;
+    let instructions = compute_budget
+        .apply_async(client, vec![instruction])
+        .await?;
+
+    let (instructions, recent_blockhash) = match (nonce, recent_blockhash) {
+        (Some(nonce), _) => (nonce.apply(instructions), nonce.blockhash),
+        (None, Some(hash)) => (instructions, hash),
+        (None, None) => (instructions, client.get_latest_blockhash().await?),
+    };
 
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
+        &instructions,
         Some(&payer.pubkey()),
         &[payer],
         recent_blockhash,
     );
 
-    let sig = client.send_and_confirm_transaction(&transaction)?;
-
-    Ok(sig)
+    Ok(transaction)
 }
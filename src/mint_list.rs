@@ -0,0 +1,502 @@
+use std::{collections::HashMap, fmt, fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+
+/// Key Sugar uses for the collection parent NFT in its cache items.
+const SUGAR_COLLECTION_KEY: &str = "-1";
+
+/// Header names recognized as the mint column when no CSV column is given.
+const MINT_COLUMN_NAMES: &[&str] = &["mint", "mint_account", "mint_address", "address", "token"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MintListFormat {
+    JsonArray,
+    NewlineDelimited,
+    Csv,
+    SugarCache,
+}
+
+impl fmt::Display for MintListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MintListFormat::JsonArray => write!(f, "JSON array"),
+            MintListFormat::NewlineDelimited => write!(f, "newline-delimited"),
+            MintListFormat::Csv => write!(f, "CSV"),
+            MintListFormat::SugarCache => write!(f, "Sugar cache"),
+        }
+    }
+}
+
+/// Where an entry came from in its mint list.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum EntryLocation {
+    /// 1-based line number in the file.
+    Line(usize),
+    /// 0-based position in a JSON array.
+    Index(usize),
+    /// Key of the item in a Sugar cache file.
+    Key(String),
+}
+
+impl fmt::Display for EntryLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryLocation::Line(line) => write!(f, "line {line}"),
+            EntryLocation::Index(index) => write!(f, "entry {index}"),
+            EntryLocation::Key(key) => write!(f, "item {key}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MintListEntry {
+    pub location: EntryLocation,
+    pub value: String,
+    /// Why the entry is invalid regardless of its value, e.g. a JSON array
+    /// element that isn't a string.
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct MintList {
+    pub format: MintListFormat,
+    pub entries: Vec<MintListEntry>,
+}
+
+/// Loads a mint list, detecting whether it is a JSON array, a Sugar cache file,
+/// CSV or one mint per line. `csv_column` selects the CSV column by header name
+/// or 0-based index.
+pub fn load_mint_list(path: &Path, csv_column: Option<&str>) -> Result<MintList> {
+    parse_mint_list(&fs::read_to_string(path)?, csv_column)
+}
+
+fn parse_mint_list(contents: &str, csv_column: Option<&str>) -> Result<MintList> {
+    let trimmed = contents.trim_start();
+
+    let mint_list = if trimmed.starts_with('[') {
+        parse_json_array(contents)?
+    } else if trimmed.starts_with('{') {
+        parse_sugar_cache(contents)?
+    } else if is_csv(contents) {
+        parse_csv(contents, csv_column)?
+    } else {
+        parse_lines(contents)
+    };
+
+    Ok(mint_list)
+}
+
+fn parse_json_array(contents: &str) -> Result<MintList> {
+    let values: Vec<Value> = serde_json::from_str(contents)?;
+
+    let entries = values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| match value {
+            Value::String(value) => MintListEntry {
+                location: EntryLocation::Index(i),
+                value,
+                error: None,
+            },
+            value => MintListEntry {
+                location: EntryLocation::Index(i),
+                value: value.to_string(),
+                error: Some("not a string".to_string()),
+            },
+        })
+        .collect();
+
+    Ok(MintList {
+        format: MintListFormat::JsonArray,
+        entries,
+    })
+}
+
+fn parse_sugar_cache(contents: &str) -> Result<MintList> {
+    let cache: Value = serde_json::from_str(contents)?;
+
+    let items = cache
+        .get("items")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("JSON object is not a Sugar cache file: missing 'items'"))?;
+
+    // The map iterates in lexicographic key order, so "10" would come before "2".
+    let mut items: Vec<_> = items.iter().collect();
+    items.sort_by_key(|(key, _)| key.parse::<u32>().unwrap_or(u32::MAX));
+
+    let mut entries = Vec::new();
+
+    for (key, item) in items {
+        if key == SUGAR_COLLECTION_KEY {
+            continue;
+        }
+
+        // Only items that have been minted carry an on-chain mint address.
+        if let Some(mint) = item.get("onChain").and_then(Value::as_str) {
+            entries.push(MintListEntry {
+                location: EntryLocation::Key(key.clone()),
+                value: mint.to_string(),
+                error: None,
+            });
+        }
+    }
+
+    Ok(MintList {
+        format: MintListFormat::SugarCache,
+        entries,
+    })
+}
+
+fn is_csv(contents: &str) -> bool {
+    contents
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.contains(','))
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    line.split(',')
+        .map(|field| field.trim().trim_matches('"').to_string())
+        .collect()
+}
+
+/// Whether the first row is a header, for when none of its fields is a known
+/// column name: it is one if its mint field isn't a pubkey while the next
+/// row's is. Any other first row is data, and validation reports it if it's
+/// malformed.
+fn looks_like_header(rows: &[(usize, Vec<String>)], index: usize) -> bool {
+    let is_pubkey = |row: Option<&(usize, Vec<String>)>| {
+        row.and_then(|(_, fields)| fields.get(index))
+            .is_some_and(|field| Pubkey::from_str(field).is_ok())
+    };
+
+    !is_pubkey(rows.first()) && is_pubkey(rows.get(1))
+}
+
+fn parse_csv(contents: &str, column: Option<&str>) -> Result<MintList> {
+    let rows: Vec<(usize, Vec<String>)> = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i, split_csv_line(line)))
+        .collect();
+
+    let first = match rows.first() {
+        Some((_, fields)) => fields,
+        None => bail!("CSV mint list is empty"),
+    };
+
+    let (index, has_header) = match column {
+        Some(column) => match column.parse::<usize>() {
+            Ok(index) => (index, looks_like_header(&rows, index)),
+            Err(_) => {
+                let index = first
+                    .iter()
+                    .position(|field| field.eq_ignore_ascii_case(column))
+                    .ok_or_else(|| anyhow!("CSV column '{column}' not found in header"))?;
+                (index, true)
+            }
+        },
+        None => {
+            let named = first.iter().position(|field| {
+                MINT_COLUMN_NAMES
+                    .iter()
+                    .any(|name| field.eq_ignore_ascii_case(name))
+            });
+
+            match named {
+                Some(index) => (index, true),
+                None => (0, looks_like_header(&rows, 0)),
+            }
+        }
+    };
+
+    let entries = rows
+        .into_iter()
+        .skip(usize::from(has_header))
+        .map(|(i, fields)| MintListEntry {
+            location: EntryLocation::Line(i + 1),
+            value: fields.into_iter().nth(index).unwrap_or_default(),
+            error: None,
+        })
+        .collect();
+
+    Ok(MintList {
+        format: MintListFormat::Csv,
+        entries,
+    })
+}
+
+fn parse_lines(contents: &str) -> MintList {
+    let entries = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| MintListEntry {
+            location: EntryLocation::Line(i + 1),
+            value: line.trim().to_string(),
+            error: None,
+        })
+        .collect();
+
+    MintList {
+        format: MintListFormat::NewlineDelimited,
+        entries,
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct InvalidEntry {
+    pub location: EntryLocation,
+    pub value: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateEntry {
+    pub location: EntryLocation,
+    pub value: String,
+    pub first_location: EntryLocation,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    #[serde(skip)]
+    pub mints: Vec<Pubkey>,
+    pub invalid: Vec<InvalidEntry>,
+    pub duplicates: Vec<DuplicateEntry>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.invalid.is_empty() && self.duplicates.is_empty()
+    }
+}
+
+impl MintList {
+    /// Parses every entry, collecting the invalid ones and dropping duplicates
+    /// after their first occurrence.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let mut seen: HashMap<Pubkey, &EntryLocation> = HashMap::new();
+
+        for entry in &self.entries {
+            let parsed = match &entry.error {
+                Some(error) => Err(error.clone()),
+                None => Pubkey::from_str(&entry.value).map_err(|e| e.to_string()),
+            };
+            let mint = match parsed {
+                Ok(mint) => mint,
+                Err(reason) => {
+                    report.invalid.push(InvalidEntry {
+                        location: entry.location.clone(),
+                        value: entry.value.clone(),
+                        reason,
+                    });
+                    continue;
+                }
+            };
+
+            match seen.get(&mint) {
+                Some(first_location) => report.duplicates.push(DuplicateEntry {
+                    location: entry.location.clone(),
+                    value: entry.value.clone(),
+                    first_location: (*first_location).clone(),
+                }),
+                None => {
+                    seen.insert(mint, &entry.location);
+                    report.mints.push(mint);
+                }
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mint_list(values: &[String]) -> MintList {
+        MintList {
+            format: MintListFormat::NewlineDelimited,
+            entries: values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| MintListEntry {
+                    location: EntryLocation::Line(i + 1),
+                    value: value.clone(),
+                    error: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn values(mint_list: &MintList) -> Vec<&str> {
+        mint_list
+            .entries
+            .iter()
+            .map(|entry| entry.value.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn parses_json_arrays() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mint_list = parse_mint_list(&format!("[\"{a}\", \"{b}\"]"), None).unwrap();
+
+        assert_eq!(mint_list.format, MintListFormat::JsonArray);
+        assert_eq!(values(&mint_list), [a.to_string(), b.to_string()]);
+        assert_eq!(mint_list.entries[1].location, EntryLocation::Index(1));
+    }
+
+    #[test]
+    fn json_array_elements_that_are_not_strings_are_invalid() {
+        let a = Pubkey::new_unique();
+
+        let mint_list = parse_mint_list(&format!("[\n  \"{a}\",\n  42\n]"), None).unwrap();
+        let report = mint_list.validate();
+
+        assert_eq!(report.mints, vec![a]);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].location, EntryLocation::Index(1));
+        assert_eq!(report.invalid[0].location.to_string(), "entry 1");
+        assert_eq!(report.invalid[0].value, "42");
+        assert_eq!(report.invalid[0].reason, "not a string");
+    }
+
+    #[test]
+    fn parses_newline_delimited_lists_skipping_blank_lines() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mint_list = parse_mint_list(&format!("{a}\n\n  {b}  \n"), None).unwrap();
+
+        assert_eq!(mint_list.format, MintListFormat::NewlineDelimited);
+        assert_eq!(values(&mint_list), [a.to_string(), b.to_string()]);
+        assert_eq!(mint_list.entries[1].location, EntryLocation::Line(3));
+    }
+
+    #[test]
+    fn csv_header_with_a_known_column_name_is_skipped() {
+        let a = Pubkey::new_unique();
+
+        let mint_list = parse_mint_list(&format!("name,mint\n\"Goose #1\",{a}\n"), None).unwrap();
+
+        assert_eq!(mint_list.format, MintListFormat::Csv);
+        assert_eq!(values(&mint_list), [a.to_string()]);
+        assert_eq!(mint_list.entries[0].location, EntryLocation::Line(2));
+    }
+
+    #[test]
+    fn csv_column_can_be_selected_by_name_or_index() {
+        let a = Pubkey::new_unique();
+        let contents = format!("id,token_mint\n1,{a}\n");
+
+        let by_name = parse_mint_list(&contents, Some("token_mint")).unwrap();
+        let by_index = parse_mint_list(&contents, Some("1")).unwrap();
+
+        assert_eq!(values(&by_name), [a.to_string()]);
+        assert_eq!(values(&by_index), [a.to_string()]);
+        assert!(parse_mint_list(&contents, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn csv_header_with_unknown_names_is_skipped_before_a_mint() {
+        let a = Pubkey::new_unique();
+
+        let mint_list = parse_mint_list(&format!("nft,holder\n{a},x\n"), None).unwrap();
+
+        assert_eq!(values(&mint_list), [a.to_string()]);
+    }
+
+    #[test]
+    fn csv_first_row_is_data_unless_it_looks_like_a_header() {
+        let a = Pubkey::new_unique();
+
+        let mint_list = parse_mint_list(&format!("{a},1\nbad,2\n"), None).unwrap();
+        assert_eq!(values(&mint_list), [a.to_string(), "bad".to_string()]);
+
+        let mint_list = parse_mint_list("bad,1\nworse,2\n", Some("0")).unwrap();
+        assert_eq!(values(&mint_list), ["bad", "worse"]);
+    }
+
+    #[test]
+    fn sugar_cache_reports_item_keys() {
+        let (collection, a) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let contents = format!(
+            r#"{{"program": {{}}, "items": {{
+                "-1": {{"name": "Collection", "onChain": "{collection}"}},
+                "0": {{"name": "Goose #1", "onChain": "{a}"}},
+                "1": {{"name": "Goose #2"}}
+            }}}}"#
+        );
+
+        let mint_list = parse_mint_list(&contents, None).unwrap();
+
+        assert_eq!(mint_list.format, MintListFormat::SugarCache);
+        assert_eq!(values(&mint_list), [a.to_string()]);
+        assert_eq!(
+            mint_list.entries[0].location,
+            EntryLocation::Key("0".to_string())
+        );
+    }
+
+    #[test]
+    fn sugar_cache_items_are_in_numeric_order() {
+        let mints: Vec<String> = (0..12).map(|_| Pubkey::new_unique().to_string()).collect();
+        let items: Vec<String> = mints
+            .iter()
+            .enumerate()
+            .map(|(i, mint)| format!(r#""{i}": {{"onChain": "{mint}"}}"#))
+            .collect();
+        let contents = format!(r#"{{"items": {{{}}}}}"#, items.join(", "));
+
+        let mint_list = parse_mint_list(&contents, None).unwrap();
+
+        assert_eq!(values(&mint_list), mints);
+        assert_eq!(
+            mint_list.entries[10].location,
+            EntryLocation::Key("10".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_keeps_mints_in_order() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let report = mint_list(&[a.to_string(), b.to_string()]).validate();
+
+        assert!(report.is_clean());
+        assert_eq!(report.mints, vec![a, b]);
+    }
+
+    #[test]
+    fn validate_reports_invalid_entries() {
+        let a = Pubkey::new_unique();
+
+        let report = mint_list(&[a.to_string(), "not-a-mint".to_string()]).validate();
+
+        assert_eq!(report.mints, vec![a]);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].location, EntryLocation::Line(2));
+        assert_eq!(report.invalid[0].value, "not-a-mint");
+    }
+
+    #[test]
+    fn validate_drops_duplicates_after_the_first() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let report = mint_list(&[a.to_string(), b.to_string(), a.to_string()]).validate();
+
+        assert_eq!(report.mints, vec![a, b]);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].location, EntryLocation::Line(3));
+        assert_eq!(report.duplicates[0].first_location, EntryLocation::Line(1));
+    }
+}
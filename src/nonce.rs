@@ -0,0 +1,65 @@
+use anyhow::{anyhow, Result};
+use solana_client::{nonce_utils, rpc_client::RpcClient};
+use solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey, system_instruction};
+use solana_sdk::nonce::State;
+
+/// A durable nonce account together with the blockhash currently stored in it.
+/// Transactions using it stay valid until the nonce is advanced, rather than
+/// expiring with the recent blockhash.
+#[derive(Clone, Copy, Debug)]
+pub struct DurableNonce {
+    pub account: Pubkey,
+    pub authority: Pubkey,
+    pub blockhash: Hash,
+}
+
+impl DurableNonce {
+    /// Reads the stored blockhash from the nonce account.
+    pub fn fetch(client: &RpcClient, account: Pubkey, authority: Pubkey) -> Result<Self> {
+        let nonce_account = client.get_account(&account)?;
+        let data = nonce_utils::data_from_account(&nonce_account)
+            .map_err(|e| anyhow!("Invalid nonce account {account}: {e}"))?;
+
+        if data.authority != authority {
+            return Err(anyhow!(
+                "Nonce account {account} has authority {}, not {authority}",
+                data.authority
+            ));
+        }
+
+        Ok(Self {
+            account,
+            authority,
+            blockhash: data.blockhash(),
+        })
+    }
+
+    /// Prepends the instruction advancing the nonce, which the runtime requires
+    /// to be the first one in the transaction.
+    pub fn apply(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut advanced = Vec::with_capacity(instructions.len() + 1);
+        advanced.push(system_instruction::advance_nonce_account(
+            &self.account,
+            &self.authority,
+        ));
+        advanced.extend(instructions);
+        advanced
+    }
+}
+
+/// Instructions creating and initializing a rent exempt nonce account.
+pub fn create_nonce_account_instructions(
+    client: &RpcClient,
+    payer: &Pubkey,
+    nonce_account: &Pubkey,
+    authority: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    let lamports = client.get_minimum_balance_for_rent_exemption(State::size())?;
+
+    Ok(system_instruction::create_nonce_account(
+        payer,
+        nonce_account,
+        authority,
+        lamports,
+    ))
+}
@@ -0,0 +1,252 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use solana_program::{
+    hash::Hash, message::Message, pubkey::Pubkey, system_instruction::SystemInstruction,
+    system_program,
+};
+use solana_sdk::{
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+
+/// One signer's signature over a message, as exchanged between machines.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartialSignature {
+    pub pubkey: String,
+    pub signature: String,
+}
+
+/// A bs58 encoded message together with the signatures collected for it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub message: String,
+    pub signatures: Vec<PartialSignature>,
+}
+
+pub fn encode_message(message: &Message) -> String {
+    bs58::encode(message.serialize()).into_string()
+}
+
+pub fn decode_message(encoded: &str) -> Result<Message> {
+    let bytes = bs58::decode(encoded.trim())
+        .into_vec()
+        .map_err(|e| anyhow!("Message is not valid bs58: {e}"))?;
+
+    bincode::deserialize(&bytes).map_err(|e| anyhow!("Failed to decode message: {e}"))
+}
+
+/// Reads a message given either directly as bs58 or as the path of a file
+/// holding it.
+pub fn read_message(input: &str) -> Result<Message> {
+    let path = Path::new(input);
+
+    if path.is_file() {
+        decode_message(&fs::read_to_string(path)?)
+    } else {
+        decode_message(input)
+    }
+}
+
+fn required_signers(message: &Message) -> &[Pubkey] {
+    &message.account_keys[..message.header.num_required_signatures as usize]
+}
+
+/// Whether the message starts by advancing a durable nonce, in which case its
+/// recent blockhash is the nonce value.
+fn uses_durable_nonce(message: &Message) -> bool {
+    message.instructions.first().is_some_and(|instruction| {
+        message
+            .account_keys
+            .get(instruction.program_id_index as usize)
+            == Some(&system_program::id())
+            && matches!(
+                bincode::deserialize::<SystemInstruction>(&instruction.data),
+                Ok(SystemInstruction::AdvanceNonceAccount)
+            )
+    })
+}
+
+/// Signs a message, first setting its recent blockhash if one is given.
+pub fn sign_message(
+    mut message: Message,
+    keypair: &Keypair,
+    recent_blockhash: Option<Hash>,
+) -> Result<SignedMessage> {
+    if let Some(recent_blockhash) = recent_blockhash {
+        if uses_durable_nonce(&message) {
+            bail!("Message uses a durable nonce, its blockhash can't be replaced with --blockhash");
+        }
+        message.recent_blockhash = recent_blockhash;
+    }
+
+    // init-msg and update-msg set the nonce as the blockhash when given a nonce
+    // account, and otherwise leave it empty.
+    if message.recent_blockhash == Hash::default() {
+        bail!("Message has no recent blockhash, pass one with --blockhash");
+    }
+
+    if !required_signers(&message).contains(&keypair.pubkey()) {
+        bail!(
+            "{} is not a required signer of this message",
+            keypair.pubkey()
+        );
+    }
+
+    let signature = keypair.sign_message(&message.serialize());
+
+    Ok(SignedMessage {
+        message: encode_message(&message),
+        signatures: vec![PartialSignature {
+            pubkey: keypair.pubkey().to_string(),
+            signature: signature.to_string(),
+        }],
+    })
+}
+
+/// Merges the signatures from several signed copies of the same message into a
+/// transaction, verifying each one and failing if a required signer is missing.
+pub fn assemble_transaction(signed: &[SignedMessage]) -> Result<Transaction> {
+    let first = signed
+        .first()
+        .ok_or_else(|| anyhow!("No signed messages given"))?;
+
+    if let Some(other) = signed.iter().find(|s| s.message != first.message) {
+        bail!(
+            "Signed messages differ, all signers must sign the same message: {} vs {}",
+            first.message,
+            other.message
+        );
+    }
+
+    let message = decode_message(&first.message)?;
+    let message_bytes = message.serialize();
+    let signers = required_signers(&message).to_vec();
+
+    let mut signatures: Vec<Option<Signature>> = vec![None; signers.len()];
+
+    for partial in signed.iter().flat_map(|s| s.signatures.iter()) {
+        let pubkey = Pubkey::from_str(&partial.pubkey)?;
+        let signature = Signature::from_str(&partial.signature)?;
+
+        let index = signers
+            .iter()
+            .position(|signer| *signer == pubkey)
+            .ok_or_else(|| anyhow!("{pubkey} is not a required signer of this message"))?;
+
+        if !signature.verify(pubkey.as_ref(), &message_bytes) {
+            bail!("Invalid signature from {pubkey}");
+        }
+
+        signatures[index] = Some(signature);
+    }
+
+    let missing: Vec<String> = signers
+        .iter()
+        .zip(&signatures)
+        .filter(|(_, signature)| signature.is_none())
+        .map(|(signer, _)| signer.to_string())
+        .collect();
+
+    if !missing.is_empty() {
+        bail!("Missing signatures from: {}", missing.join(", "));
+    }
+
+    Ok(Transaction {
+        signatures: signatures.into_iter().flatten().collect(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::{
+        instruction::{AccountMeta, Instruction},
+        system_instruction,
+    };
+
+    use super::*;
+
+    fn two_signer_message(payer: &Keypair, authority: &Keypair) -> Message {
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[1, 2, 3],
+            vec![AccountMeta::new_readonly(authority.pubkey(), true)],
+        );
+
+        Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &Hash::new_unique())
+    }
+
+    #[test]
+    fn assembles_signatures_in_signer_order() {
+        let (payer, authority) = (Keypair::new(), Keypair::new());
+        let message = two_signer_message(&payer, &authority);
+
+        let signed = [
+            sign_message(message.clone(), &authority, None).unwrap(),
+            sign_message(message.clone(), &payer, None).unwrap(),
+        ];
+        let transaction = assemble_transaction(&signed).unwrap();
+
+        assert_eq!(transaction.message, message);
+        assert!(transaction.verify().is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_signers() {
+        let (payer, authority) = (Keypair::new(), Keypair::new());
+        let message = two_signer_message(&payer, &authority);
+
+        let signed = [sign_message(message, &payer, None).unwrap()];
+        let error = assemble_transaction(&signed).unwrap_err();
+
+        assert!(error.to_string().contains(&authority.pubkey().to_string()));
+    }
+
+    #[test]
+    fn rejects_blockhash_for_nonce_messages() {
+        let (payer, nonce) = (Keypair::new(), Pubkey::new_unique());
+        let instructions = [
+            system_instruction::advance_nonce_account(&nonce, &payer.pubkey()),
+            system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1),
+        ];
+        let message =
+            Message::new_with_blockhash(&instructions, Some(&payer.pubkey()), &Hash::new_unique());
+
+        assert!(sign_message(message.clone(), &payer, None).is_ok());
+
+        let error = sign_message(message, &payer, Some(Hash::new_unique())).unwrap_err();
+
+        assert!(error.to_string().contains("durable nonce"));
+    }
+
+    #[test]
+    fn rejects_different_messages() {
+        let (payer, authority) = (Keypair::new(), Keypair::new());
+
+        let signed = [
+            sign_message(two_signer_message(&payer, &authority), &payer, None).unwrap(),
+            sign_message(two_signer_message(&payer, &authority), &authority, None).unwrap(),
+        ];
+
+        assert!(assemble_transaction(&signed).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_signatures() {
+        let (payer, authority) = (Keypair::new(), Keypair::new());
+        let message = two_signer_message(&payer, &authority);
+
+        let mut signed = [
+            sign_message(message.clone(), &payer, None).unwrap(),
+            sign_message(message, &authority, None).unwrap(),
+        ];
+        signed[1].signatures[0].signature = Signature::default().to_string();
+
+        let error = assemble_transaction(&signed).unwrap_err();
+
+        assert!(error.to_string().contains("Invalid signature"));
+    }
+}
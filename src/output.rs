@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::args::OutputFormat;
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Sets the output format for the whole process. Call once at startup.
+pub fn set_output_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Prints a line of human readable output, which is suppressed in JSON mode.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_json() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a command's result as a single JSON object in JSON mode.
+pub fn emit(value: &Value) -> Result<()> {
+    if is_json() {
+        println!("{}", serde_json::to_string(value)?);
+    }
+
+    Ok(())
+}
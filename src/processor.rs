@@ -1,8 +1,15 @@
-use std::{fs::File, path::PathBuf, str::FromStr, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use ::futures::stream::FuturesUnordered;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use borsh::BorshDeserialize;
+use chrono::Utc;
 use console::style;
 use futures::StreamExt;
 use mpl_migration_validator::{
@@ -10,72 +17,268 @@ use mpl_migration_validator::{
     utils::find_migration_state_pda,
     PROGRAM_SIGNER,
 };
-use mpl_token_metadata::{
-    pda::find_metadata_account,
-    state::{Metadata, TokenMetadataAccount, TokenStandard},
-};
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
-use solana_program::{
-    bpf_loader_upgradeable::UpgradeableLoaderState, program_pack::Pack, pubkey::Pubkey,
-};
+use serde_json::{json, Value};
+use solana_client::{nonblocking::rpc_client::RpcClient as AsyncRpcClient, rpc_client::RpcClient};
+use solana_program::{program_pack::Pack, pubkey::Pubkey, system_program};
 use solana_sdk::{
-    signature::{Keypair, Signature},
+    hash::Hash,
+    signature::{read_keypair_file, write_keypair_file, Keypair, Signature},
     signer::Signer,
     transaction::Transaction,
 };
 use spl_token::state::Account as TokenAccount;
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
 
 use crate::{
+    args::{SendMode, UnlockMethodArg},
+    blockhash::{
+        spawn_blockhash_refresher, BlockhashCache, RefreshFailures, BLOCKHASH_REFRESH_INTERVAL,
+    },
+    compute_budget::ComputeBudget,
+    confirm::{ConfirmationQueue, CONFIRMATION_POLL_INTERVAL},
+    display::{format_countdown, format_timestamp, print_migration_state},
+    eligibility::{check_metadata, check_token_account, Ineligibility},
+    errors::TokenAccountError,
+    inspect::{decode_serialized, summarize_accounts, summarize_instructions},
+    journal::{journal_path, read_journal, Journal, JournalEntry},
     methods::{
-        close, get_state, initialize, initialize_msg, migrate_item, start, update, update_msg,
-        CloseParams, GetStateParams, InitializeMsgParams, InitializeParams, MigrateParams,
-        StartParams, UpdateMsgParams, UpdateParams,
+        close, create_nonce_account, get_state, get_state_async, initialize, initialize_msg,
+        migrate_item_async, migrate_item_transaction, send_or_simulate, snapshot, start, update,
+        update_msg, CloseParams, CreateNonceParams, GetStateAsyncParams, GetStateParams,
+        InitializeMsgParams, InitializeParams, MigrateAsyncParams, Simulation, SnapshotParams,
+        StartParams, TxOutcome, UpdateMsgParams, UpdateParams,
+    },
+    mint_list::{load_mint_list, ValidationReport},
+    offline::{assemble_transaction, read_message, sign_message, SignedMessage},
+    output::emit,
+    rate_limit::{RateLimitStats, RateLimiter},
+    retry::{classify, with_retry, ErrorClass, RetryPolicy},
+    run_report::{RunReport, RunStep, StepStatus},
+    say,
+    setup::{load_keypair, message_nonce, rpc_client, CliConfig, GlobalOptions},
+    squads::SquadsProposal,
+    utils::{
+        create_progress_bar, get_cluster, get_cluster_time, get_cluster_time_async,
+        get_metadata_async, get_nft_token_account_async, get_token_owner_program_async,
+        is_programmable, spinner_with_style,
     },
-    setup,
-    utils::{create_progress_bar, get_cluster, get_nft_token_account, spinner_with_style},
 };
 
+/// Prints a transaction outcome and returns it as JSON for the command's result.
+fn print_tx_outcome(client: &RpcClient, outcome: &TxOutcome, action: &str) -> Result<Value> {
+    match outcome {
+        TxOutcome::Sent(sig) => {
+            let cluster = get_cluster(client)?;
+            let link = format!("https://explorer.solana.com/tx/{sig}?cluster={cluster}");
+            say!("{action} successfully in tx: {}", style(&link).green());
+
+            Ok(json!({
+                "signature": sig.to_string(),
+                "explorer": link,
+            }))
+        }
+        TxOutcome::Simulated(simulation) => {
+            say!("{}", style(format!("Dry run: {action}")).yellow());
+            print_simulation(simulation);
+
+            Ok(json!({ "simulation": simulation }))
+        }
+    }
+}
+
+/// Action to report for a transaction, which only creates a proposal when the
+/// authority is a Squads multisig.
+fn tx_action(done: &str, proposed: &str, multisig: Option<SquadsProposal>) -> String {
+    match multisig {
+        Some(proposal) => format!("Created proposal {} to {proposed}", proposal.proposal()),
+        None => done.to_string(),
+    }
+}
+
+/// Only init, update, start and cancel can be proposed to a multisig.
+fn reject_multisig(options: &GlobalOptions, command: &str) -> Result<()> {
+    if options.multisig.is_some() {
+        bail!("{command} doesn't support --multisig");
+    }
+
+    Ok(())
+}
+
+/// Prints the accounts of a Squads proposal for members to approve and adds
+/// them to the command's result.
+fn with_proposal(mut result: Value, multisig: Option<SquadsProposal>) -> Value {
+    if let Some(proposal) = multisig {
+        say!(
+            "Proposal {} for vault transaction #{} of multisig {}",
+            style(proposal.proposal()).green(),
+            proposal.transaction_index,
+            proposal.multisig
+        );
+        say!("Members need to approve and execute it before it takes effect.");
+
+        result["proposal"] = json!({
+            "multisig": proposal.multisig.to_string(),
+            "vault": proposal.vault().to_string(),
+            "transaction_index": proposal.transaction_index,
+            "transaction": proposal.transaction().to_string(),
+            "proposal": proposal.proposal().to_string(),
+        });
+    }
+
+    result
+}
+
+/// Prints a migration state and returns it as JSON, along with its address and
+/// the unlock countdown against cluster time.
+fn print_state(
+    client: &RpcClient,
+    collection_mint: &Pubkey,
+    state: &MigrationState,
+) -> Result<Value> {
+    let (address, _) = find_migration_state_pda(collection_mint);
+    let cluster_time = get_cluster_time(client)?;
+
+    print_migration_state(state, &address, cluster_time);
+
+    let mut value = serde_json::to_value(state)?;
+    value["address"] = json!(address.to_string());
+    value["cluster_time"] = json!(cluster_time);
+    value["unlock_time_utc"] = json!(format_timestamp(state.status.unlock_time));
+    value["seconds_until_unlock"] = json!((state.status.unlock_time - cluster_time).max(0));
+
+    Ok(value)
+}
+
+fn print_simulation(simulation: &Simulation) {
+    match &simulation.err {
+        Some(err) => say!("Simulation failed: {}", style(err).red()),
+        None => say!("Simulation {}", style("succeeded").green()),
+    }
+
+    if let Some(units) = simulation.units_consumed {
+        say!("Compute units consumed: {units}");
+    }
+
+    say!("Program logs:");
+    for log in &simulation.logs {
+        say!("  {log}");
+    }
+}
+
+fn rate_limit_json(stats: RateLimitStats) -> Value {
+    json!({
+        "requests": stats.requests,
+        "throttled": stats.throttled,
+        "waited_secs": stats.waited.as_secs_f64(),
+    })
+}
+
+fn print_refresh_failures(failures: &RefreshFailures) {
+    if let Some(error) = &failures.last_error {
+        say!(
+            "{} blockhash refreshes failed, the last one with: {error}",
+            failures.count
+        );
+    }
+}
+
+fn print_validation_report(report: &ValidationReport) {
+    for invalid in &report.invalid {
+        say!(
+            "{} {}: '{}' ({})",
+            style("Invalid").red(),
+            invalid.location,
+            invalid.value,
+            invalid.reason
+        );
+    }
+
+    for duplicate in &report.duplicates {
+        say!(
+            "{} {}: {} (first seen on {})",
+            style("Duplicate").yellow(),
+            duplicate.location,
+            duplicate.value,
+            duplicate.first_location
+        );
+    }
+
+    say!(
+        "{} valid mints, {} invalid entries, {} duplicates removed",
+        report.mints.len(),
+        report.invalid.len(),
+        report.duplicates.len()
+    );
+}
+
+/// Loads and validates a mint list. Refuses to continue if it has invalid
+/// entries unless `skip_invalid` is set.
+fn load_mints(path: &Path, mint_column: Option<&str>, skip_invalid: bool) -> Result<Vec<Pubkey>> {
+    let mint_list = load_mint_list(path, mint_column)?;
+    say!(
+        "Loaded {} entries from {} mint list",
+        mint_list.entries.len(),
+        mint_list.format
+    );
+
+    let report = mint_list.validate();
+    if !report.is_clean() {
+        print_validation_report(&report);
+    }
+
+    if !report.invalid.is_empty() && !skip_invalid {
+        bail!(
+            "Mint list has {} invalid entries. Fix them or pass --skip-invalid to ignore them.",
+            report.invalid.len()
+        );
+    }
+
+    Ok(report.mints)
+}
+
 pub fn process_initialize(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
+    options: &GlobalOptions,
     collection_mint: Pubkey,
-    unlock_method: String,
+    unlock_method: UnlockMethodArg,
     collection_size: u32,
 ) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
-
-    let unlock_method = match unlock_method.to_lowercase().as_str() {
-        "timed" => UnlockMethod::Timed,
-        "vote" => UnlockMethod::Vote,
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Invalid unlock method. Must be one of: Timed, Vote"
-            ))
-        }
-    };
+    let config = CliConfig::new(options)?;
+    let multisig = config.squads_proposal()?;
 
     let params = InitializeParams {
         client: &config.client,
-        payer: &config.keypair,
+        payer: config.payer(),
         authority: &config.keypair,
         rule_set: None,
         collection_mint,
-        unlock_method,
+        unlock_method: unlock_method.into(),
         collection_size,
+        recent_blockhash: Some(config.recent_blockhash),
+        compute_budget: config.compute_budget,
+        nonce: config.durable_nonce()?,
+        multisig,
+        dry_run: config.dry_run,
     };
     let spinner = spinner_with_style();
     spinner.set_message("Initializing migration state...");
-    let sig = initialize(params)?;
+    let outcome = initialize(params)?;
     spinner.finish();
 
-    let cluster = get_cluster(&config.client)?;
-    let link = format!("https://explorer.solana.com/tx/{sig}?cluster={cluster}");
-    println!(
-        "Intialized migration state successfully in tx: {}",
-        style(link).green()
+    let action = tx_action(
+        "Intialized migration state",
+        "initialize migration state",
+        multisig,
     );
+    let transaction = print_tx_outcome(&config.client, &outcome, &action)?;
+
+    // Nothing was created on chain yet, so there is no state to fetch.
+    if matches!(outcome, TxOutcome::Simulated(_)) || multisig.is_some() {
+        return emit(&with_proposal(
+            json!({ "transaction": transaction }),
+            multisig,
+        ));
+    }
 
     // Delay before fetching the state.
     let spinner = spinner_with_style();
@@ -91,26 +294,27 @@ pub fn process_initialize(
     let state = get_state(get_state_params)?;
     spinner.finish();
 
-    println!("Migration state:\n {:#?}", style(state).green());
+    let state = print_state(&config.client, &collection_mint, &state)?;
 
-    Ok(())
+    emit(&json!({
+        "transaction": transaction,
+        "state": state,
+    }))
 }
 
 pub fn process_initialize_msg(
+    options: &GlobalOptions,
     payer: Pubkey,
     authority: Pubkey,
     collection_mint: Pubkey,
-    unlock_method: String,
+    unlock_method: UnlockMethodArg,
     collection_size: u32,
 ) -> Result<()> {
-    let unlock_method = match unlock_method.to_lowercase().as_str() {
-        "timed" => UnlockMethod::Timed,
-        "vote" => UnlockMethod::Vote,
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Invalid unlock method. Must be one of: Timed, Vote"
-            ))
-        }
+    reject_multisig(options, "init-msg")?;
+    // Only connect to the RPC server when the message needs a nonce.
+    let nonce = match options.nonce_account {
+        Some(_) => message_nonce(&rpc_client(options)?, options, payer)?,
+        None => None,
     };
 
     let params = InitializeMsgParams {
@@ -118,77 +322,93 @@ pub fn process_initialize_msg(
         authority,
         rule_set: None,
         collection_mint,
-        unlock_method,
+        unlock_method: unlock_method.into(),
         collection_size,
+        compute_budget: options.compute_budget,
+        nonce,
     };
     let spinner = spinner_with_style();
     spinner.set_message("Initializing migration state...");
     let message = initialize_msg(params)?;
     spinner.finish();
 
-    println!("Transaction message:\n {:#?}", style(message).green());
+    say!("Transaction message:\n {:#?}", style(&message).green());
 
-    Ok(())
+    emit(&json!({ "message": message }))
 }
 
-pub fn process_initialize_signer(keypair: Option<PathBuf>, rpc_url: Option<String>) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
-
-    let instruction = mpl_migration_validator::instruction::init_signer(config.keypair.pubkey());
-    let spinner = spinner_with_style();
-    spinner.set_message("Initializing program signer...");
-    let recent_blockhash = config.client.get_latest_blockhash()?;
+fn send_init_signer(config: &CliConfig) -> Result<TxOutcome> {
+    let payer = config.payer();
+    let instruction = mpl_migration_validator::instruction::init_signer(payer.pubkey());
+    let instructions = config
+        .compute_budget
+        .apply(&config.client, vec![instruction])?;
+    let nonce = config.durable_nonce()?;
+    let (instructions, recent_blockhash) = match nonce {
+        Some(nonce) => (nonce.apply(instructions), nonce.blockhash),
+        None => (instructions, config.recent_blockhash),
+    };
 
+    // The authority keypair only signs when it advances the nonce.
+    let mut signers = vec![payer];
+    if nonce.is_some_and(|nonce| nonce.authority != payer.pubkey()) {
+        signers.push(&config.keypair);
+    }
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
-        Some(&config.keypair.pubkey()),
-        &[&config.keypair],
+        &instructions,
+        Some(&payer.pubkey()),
+        &signers,
         recent_blockhash,
     );
 
-    let sig = config.client.send_and_confirm_transaction(&transaction)?;
+    send_or_simulate(&config.client, &transaction, config.dry_run)
+}
+
+pub fn process_initialize_signer(options: &GlobalOptions) -> Result<()> {
+    reject_multisig(options, "init-signer")?;
+    let config = CliConfig::new(options)?;
+
+    let spinner = spinner_with_style();
+    spinner.set_message("Initializing program signer...");
+    let outcome = send_init_signer(&config)?;
     spinner.finish();
-    println!(
-        "Initialized program signer successfully in tx: {}",
-        style(sig).green()
-    );
 
-    Ok(())
+    let transaction = print_tx_outcome(&config.client, &outcome, "Initialized program signer")?;
+
+    emit(&json!({ "transaction": transaction }))
 }
 
-pub fn process_close(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
-    collection_mint: Pubkey,
-) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+pub fn process_close(options: &GlobalOptions, collection_mint: Pubkey) -> Result<()> {
+    let config = CliConfig::new(options)?;
+    let multisig = config.squads_proposal()?;
 
     let params = CloseParams {
         client: &config.client,
+        payer: config.payer(),
         authority: &config.keypair,
         collection_mint,
+        recent_blockhash: Some(config.recent_blockhash),
+        compute_budget: config.compute_budget,
+        nonce: config.durable_nonce()?,
+        multisig,
+        dry_run: config.dry_run,
     };
     let spinner = spinner_with_style();
     spinner.set_message("Canceling migration...");
-    let sig = close(params)?;
+    let outcome = close(params)?;
     spinner.finish();
 
-    let cluster = get_cluster(&config.client)?;
-    let link = format!("https://explorer.solana.com/tx/{sig}?cluster={cluster}");
-    println!(
-        "Canceled migration successfully in tx: {}",
-        style(link).green()
-    );
+    let action = tx_action("Canceled migration", "cancel migration", multisig);
+    let transaction = print_tx_outcome(&config.client, &outcome, &action)?;
 
-    Ok(())
+    emit(&with_proposal(
+        json!({ "transaction": transaction }),
+        multisig,
+    ))
 }
 
-pub fn process_get_state(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
-    collection_mint: Pubkey,
-) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+pub fn process_get_state(options: &GlobalOptions, collection_mint: Pubkey) -> Result<()> {
+    let config = CliConfig::new(options)?;
 
     let get_state_params = GetStateParams {
         client: &config.client,
@@ -196,13 +416,13 @@ pub fn process_get_state(
     };
     let state = get_state(get_state_params)?;
 
-    println!("Migration state:\n {:#?}", style(state).green());
+    let state = print_state(&config.client, &collection_mint, &state)?;
 
-    Ok(())
+    emit(&json!({ "state": state }))
 }
 
-pub fn process_get_all_states(keypair: Option<PathBuf>, rpc_url: Option<String>) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+pub fn process_get_all_states(options: &GlobalOptions) -> Result<()> {
+    let config = CliConfig::new(options)?;
 
     // Get all the program accounts for mpl-migration-validator.
     let account_results = config
@@ -211,7 +431,7 @@ pub fn process_get_all_states(keypair: Option<PathBuf>, rpc_url: Option<String>)
 
     let cluster = get_cluster(&config.client)?;
 
-    println!(
+    say!(
         "Found: {}",
         style(format!("{} states", account_results.len() - 1)).green()
     );
@@ -230,7 +450,7 @@ pub fn process_get_all_states(keypair: Option<PathBuf>, rpc_url: Option<String>)
             match <MigrationState as BorshDeserialize>::deserialize(&mut account.data.as_slice()) {
                 Ok(state) => state,
                 Err(_) => {
-                    println!("Failed to deserialize state for account {pubkey:?}");
+                    say!("Failed to deserialize state for account {pubkey:?}");
                     continue;
                 }
             };
@@ -240,106 +460,123 @@ pub fn process_get_all_states(keypair: Option<PathBuf>, rpc_url: Option<String>)
     let f = File::create(&file_name)?;
     serde_json::to_writer_pretty(f, &states)?;
 
-    println!(
+    say!(
         "{}",
         style(format!("Wrote migration states to {file_name}")).green()
     );
 
-    Ok(())
+    emit(&json!({
+        "cluster": cluster.to_string(),
+        "count": states.len(),
+        "file": file_name,
+    }))
 }
 
 pub fn process_update(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
+    options: &GlobalOptions,
     collection_mint: Pubkey,
     rule_set: Option<Pubkey>,
     collection_size: Option<u32>,
     new_update_authority: Option<Pubkey>,
 ) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+    let config = CliConfig::new(options)?;
+    let multisig = config.squads_proposal()?;
 
     let (migration_state, _) = find_migration_state_pda(&collection_mint);
 
     let params = UpdateParams {
         client: &config.client,
+        payer: config.payer(),
         authority: &config.keypair,
         migration_state,
         collection_size,
         rule_set,
         new_update_authority,
+        recent_blockhash: Some(config.recent_blockhash),
+        compute_budget: config.compute_budget,
+        nonce: config.durable_nonce()?,
+        multisig,
+        dry_run: config.dry_run,
     };
     let spinner = spinner_with_style();
     spinner.set_message("Updating migration state...");
-    let sig = update(params)?;
+    let outcome = update(params)?;
     spinner.finish();
 
-    let cluster = get_cluster(&config.client)?;
-    let link = format!("https://explorer.solana.com/tx/{sig}?cluster={cluster}");
-    println!(
-        "Updated migration state successfully in tx: {}",
-        style(link).green()
+    let action = tx_action(
+        "Updated migration state",
+        "update migration state",
+        multisig,
     );
+    let transaction = print_tx_outcome(&config.client, &outcome, &action)?;
 
-    Ok(())
+    emit(&with_proposal(
+        json!({ "transaction": transaction }),
+        multisig,
+    ))
 }
 
 pub fn process_update_msg(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
+    options: &GlobalOptions,
     collection_mint: Pubkey,
     rule_set: Option<Pubkey>,
     collection_size: Option<u32>,
     new_update_authority: Option<Pubkey>,
     authority_pubkey: Pubkey,
 ) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+    reject_multisig(options, "update-msg")?;
+    let config = CliConfig::new(options)?;
 
     let (migration_state, _) = find_migration_state_pda(&collection_mint);
 
     let params = UpdateMsgParams {
-        authority: &config.keypair,
+        payer: config.payer().pubkey(),
         authority_pubkey,
         migration_state,
         collection_size,
         rule_set,
         new_update_authority,
+        compute_budget: config.compute_budget,
+        nonce: message_nonce(&config.client, options, config.payer().pubkey())?,
     };
     let spinner = spinner_with_style();
     spinner.set_message("Updating migration state...");
     let tx = update_msg(params)?;
     spinner.finish();
 
-    println!("Transaction: {}", style(tx).green());
+    say!("Transaction: {}", style(&tx).green());
 
-    Ok(())
+    emit(&json!({ "transaction": tx }))
 }
 
-pub fn process_start(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
-    collection_mint: Pubkey,
-) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+pub fn process_start(options: &GlobalOptions, collection_mint: Pubkey) -> Result<()> {
+    let config = CliConfig::new(options)?;
+    let multisig = config.squads_proposal()?;
 
     let params = StartParams {
         client: &config.client,
+        payer: config.payer(),
         authority: &config.keypair,
         collection_mint,
+        recent_blockhash: Some(config.recent_blockhash),
+        compute_budget: config.compute_budget,
+        nonce: config.durable_nonce()?,
+        multisig,
+        dry_run: config.dry_run,
     };
 
     let spinner = spinner_with_style();
     spinner.set_message("Enabling migration...");
-    let sig = start(params)?;
+    let outcome = start(params)?;
     spinner.finish();
 
-    let cluster = get_cluster(&config.client)?;
-    let link = format!("https://explorer.solana.com/tx/{sig}?cluster={cluster}");
-    println!(
-        "Started migration successfully in tx: {}",
-        style(link).green()
-    );
+    let action = tx_action("Started migration", "start migration", multisig);
+    let transaction = print_tx_outcome(&config.client, &outcome, &action)?;
 
-    Ok(())
+    emit(&with_proposal(
+        json!({ "transaction": transaction }),
+        multisig,
+    ))
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -348,40 +585,175 @@ pub struct MigratedMint {
     item_mint: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IneligibleMint {
+    item_mint: String,
+    reasons: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SimulatedMint {
+    item_mint: String,
+    simulation: Simulation,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MigrationError {
     mint: String,
     error: String,
+    #[serde(default)]
+    attempts: u32,
+    #[serde(default)]
+    class: ErrorClass,
 }
 
+/// A nonce can only be used by one transaction at a time, while migrations
+/// send many in parallel.
+fn reject_nonce(options: &GlobalOptions) -> Result<()> {
+    if options.nonce_account.is_some() {
+        bail!("Durable nonces are not supported when migrating items");
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn process_migrate(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
+    options: &GlobalOptions,
     collection_mint: Pubkey,
     mint_list: PathBuf,
+    mint_column: Option<String>,
+    skip_invalid: bool,
     batch_size: usize,
+    resume: bool,
+    retry_policy: RetryPolicy,
+    send_mode: SendMode,
+    max_rps: Option<u32>,
+    wait_for_unlock: bool,
 ) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+    let summary = migrate_collection(
+        options,
+        collection_mint,
+        mint_list,
+        mint_column,
+        skip_invalid,
+        batch_size,
+        resume,
+        retry_policy,
+        send_mode,
+        max_rps,
+        wait_for_unlock,
+    )
+    .await?;
+
+    emit(&summary)
+}
 
-    let f = File::open(mint_list)?;
-    let mints: Vec<String> = serde_json::from_reader(f)?;
-    let mints: Vec<Pubkey> = mints
-        .into_iter()
-        .map(|s| Pubkey::from_str(&s).unwrap())
-        .collect();
+#[allow(clippy::too_many_arguments)]
+async fn migrate_collection(
+    options: &GlobalOptions,
+    collection_mint: Pubkey,
+    mint_list: PathBuf,
+    mint_column: Option<String>,
+    skip_invalid: bool,
+    batch_size: usize,
+    resume: bool,
+    retry_policy: RetryPolicy,
+    send_mode: SendMode,
+    max_rps: Option<u32>,
+    wait_for_unlock: bool,
+) -> Result<Value> {
+    reject_nonce(options)?;
+    reject_multisig(options, "migrate")?;
+
+    let config = CliConfig::new(options)?;
+
+    let mut mints = load_mints(&mint_list, mint_column.as_deref(), skip_invalid)?;
+
+    let journal_path = journal_path(&collection_mint);
+    let mut previously_migrated = Vec::new();
+    let mut previously_already_migrated = Vec::new();
+
+    if resume {
+        for entry in read_journal(&journal_path)? {
+            // Failed and ineligible mints are checked again, so only successes
+            // carry over.
+            match entry {
+                JournalEntry::Migrated(migrated) => previously_migrated.push(migrated),
+                JournalEntry::AlreadyMigrated { item_mint } => {
+                    previously_already_migrated.push(item_mint)
+                }
+                JournalEntry::Ineligible(_) | JournalEntry::Failed(_) => {}
+            }
+        }
 
-    let migrate_state = get_state(GetStateParams {
-        client: &config.client,
+        let done: HashSet<&str> = previously_migrated
+            .iter()
+            .map(|m| m.item_mint.as_str())
+            .chain(previously_already_migrated.iter().map(String::as_str))
+            .collect();
+        mints.retain(|mint| !done.contains(mint.to_string().as_str()));
+
+        say!(
+            "Resuming from {}: {} mints already done, {} remaining",
+            journal_path.display(),
+            done.len(),
+            mints.len()
+        );
+    }
+
+    // A dry run must not touch the journal a later real run resumes from.
+    let journal = if config.dry_run {
+        None
+    } else {
+        Some(Arc::new(Mutex::new(Journal::open(&journal_path, resume)?)))
+    };
+
+    let limiter = max_rps.map(|rps| Arc::new(RateLimiter::new(rps)));
+    let client = Arc::new(config.limited_async_client(limiter.clone()));
+
+    let mut migrate_state = get_state_async(GetStateAsyncParams {
+        client: &client,
         collection_mint,
-    })?;
+    })
+    .await?;
+
+    if wait_for_unlock {
+        wait_until_unlocked(&client, &migrate_state).await?;
+
+        // The authority may have updated the state while we were waiting.
+        migrate_state = get_state_async(GetStateAsyncParams {
+            client: &client,
+            collection_mint,
+        })
+        .await?;
+    }
 
     let rule_set = migrate_state.collection_info.rule_set;
-
-    let completed_mints: Arc<Mutex<Vec<MigratedMint>>> = Arc::new(Mutex::new(Vec::new()));
+    let authority = migrate_state.collection_info.authority;
+    let compute_budget = config.compute_budget;
+    let dry_run = config.dry_run;
+
+    let completed_mints: Arc<Mutex<Vec<MigratedMint>>> = Arc::new(Mutex::new(previously_migrated));
+    let already_migrated_mints: Arc<Mutex<Vec<String>>> =
+        Arc::new(Mutex::new(previously_already_migrated));
+    let ineligible_mints: Arc<Mutex<Vec<IneligibleMint>>> = Arc::new(Mutex::new(Vec::new()));
     let errors: Arc<Mutex<Vec<MigrationError>>> = Arc::new(Mutex::new(Vec::new()));
+    let simulations: Arc<Mutex<Vec<SimulatedMint>>> = Arc::new(Mutex::new(Vec::new()));
 
-    let keypair = Arc::new(config.keypair);
-    let client = Arc::new(config.client);
+    let payer = Arc::new(config.fee_payer.unwrap_or(config.keypair));
+
+    let (blockhash, blockhash_refresher) =
+        spawn_blockhash_refresher(client.clone(), BLOCKHASH_REFRESH_INTERVAL).await?;
+
+    let (confirmations, confirmation_worker) = match send_mode {
+        SendMode::Confirm => (None, None),
+        SendMode::Batch => {
+            let (queue, worker) =
+                ConfirmationQueue::spawn(client.clone(), CONFIRMATION_POLL_INTERVAL, batch_size);
+            (Some(queue), Some(worker))
+        }
+    };
 
     let mut tasks = FuturesUnordered::new();
     let semaphore = Arc::new(Semaphore::new(batch_size));
@@ -390,100 +762,349 @@ pub async fn process_migrate(
     pb.set_message("Migrating mints...");
     for item_mint in mints {
         let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
+        let permit = ItemPermit::new(semaphore.clone(), permit);
         let pb = pb.clone();
         let completed_mints = completed_mints.clone();
+        let already_migrated_mints = already_migrated_mints.clone();
+        let ineligible_mints = ineligible_mints.clone();
         let errors = errors.clone();
-        let keypair = keypair.clone();
+        let simulations = simulations.clone();
+        let journal = journal.clone();
+        let payer = payer.clone();
         let client = client.clone();
+        let blockhash = blockhash.clone();
+        let confirmations = confirmations.clone();
 
         tasks.push(tokio::spawn(async move {
-            let _permit = permit;
-
             let args = MigrateArgs {
-                keypair,
+                payer,
                 client,
+                blockhash,
+                permit,
+                confirmations,
+                compute_budget,
+                dry_run,
                 collection_mint,
                 item_mint,
                 rule_set,
+                authority,
             };
-            match migrate_mint(args).await {
-                Ok(sig) => {
-                    completed_mints.lock().await.push(MigratedMint {
-                        sig: sig.to_string(),
+            let result = with_retry(&retry_policy, || migrate_mint(args.clone())).await;
+
+            let entry = match result {
+                Ok(MigrateOutcome::Simulated(simulation)) => {
+                    simulations.lock().await.push(SimulatedMint {
                         item_mint: item_mint.to_string(),
+                        simulation,
                     });
+                    pb.inc(1);
+                    return Ok(());
                 }
-                Err(e) => {
-                    errors.lock().await.push(MigrationError {
-                        mint: item_mint.to_string(),
-                        error: e.to_string(),
-                    });
+                Ok(MigrateOutcome::Migrated(sig)) => JournalEntry::Migrated(MigratedMint {
+                    sig: sig.to_string(),
+                    item_mint: item_mint.to_string(),
+                }),
+                Ok(MigrateOutcome::AlreadyMigrated) => JournalEntry::AlreadyMigrated {
+                    item_mint: item_mint.to_string(),
+                },
+                Ok(MigrateOutcome::Ineligible(reasons)) => {
+                    JournalEntry::Ineligible(IneligibleMint {
+                        item_mint: item_mint.to_string(),
+                        reasons,
+                    })
+                }
+                Err(e) => JournalEntry::Failed(MigrationError {
+                    mint: item_mint.to_string(),
+                    error: e.error.to_string(),
+                    attempts: e.attempts,
+                    class: e.class,
+                }),
+            };
+
+            // Record the result before anything else so it survives an interrupted run.
+            if let Some(journal) = &journal {
+                journal.lock().await.record(&entry)?;
+            }
+
+            match entry {
+                JournalEntry::Migrated(migrated) => completed_mints.lock().await.push(migrated),
+                JournalEntry::AlreadyMigrated { item_mint } => {
+                    already_migrated_mints.lock().await.push(item_mint)
                 }
+                JournalEntry::Ineligible(ineligible) => {
+                    ineligible_mints.lock().await.push(ineligible)
+                }
+                JournalEntry::Failed(error) => errors.lock().await.push(error),
             }
 
             pb.inc(1);
+
+            Ok::<(), anyhow::Error>(())
         }));
     }
 
     while let Some(task) = tasks.next().await {
-        task?;
+        task??;
+    }
+    blockhash_refresher.abort();
+    let refresh_failures = blockhash.refresh_failures();
+
+    // All tasks have finished, so dropping the last queue handle lets the
+    // confirmation worker exit.
+    drop(confirmations);
+    if let Some(worker) = confirmation_worker {
+        worker.await?;
     }
 
     let completed_mints = Arc::try_unwrap(completed_mints).unwrap().into_inner();
+    let already_migrated_mints = Arc::try_unwrap(already_migrated_mints)
+        .unwrap()
+        .into_inner();
+    let ineligible_mints = Arc::try_unwrap(ineligible_mints).unwrap().into_inner();
     let errors = Arc::try_unwrap(errors).unwrap().into_inner();
+    let simulations = Arc::try_unwrap(simulations).unwrap().into_inner();
+
+    if dry_run {
+        let failed_simulations = simulations
+            .iter()
+            .filter(|s| s.simulation.err.is_some())
+            .count();
+
+        say!(
+            "Simulated {} mints, {} of which failed",
+            simulations.len(),
+            failed_simulations
+        );
+        say!(
+            "Skipped {} already migrated mints",
+            already_migrated_mints.len()
+        );
+        say!("Found {} ineligible mints", ineligible_mints.len());
+        say!("Failed to simulate {} mints", errors.len());
+        if let Some(limiter) = &limiter {
+            say!("Rate limit: {}", limiter.stats());
+        }
+        print_refresh_failures(&refresh_failures);
+
+        let report_name = format!("{collection_mint}_simulation.json");
+        let f = File::create(&report_name)?;
+        serde_json::to_writer_pretty(
+            f,
+            &json!({
+                "simulated": simulations,
+                "already_migrated": already_migrated_mints,
+                "ineligible": ineligible_mints,
+                "failed": errors,
+            }),
+        )?;
+        say!("Wrote simulation report to {report_name}");
+
+        return Ok(json!({
+            "dry_run": true,
+            "simulated": simulations.len(),
+            "failed_simulations": failed_simulations,
+            "already_migrated": already_migrated_mints.len(),
+            "ineligible": ineligible_mints.len(),
+            "failed": errors.len(),
+            "rate_limit": limiter.as_ref().map(|limiter| rate_limit_json(limiter.stats())),
+            "blockhash_refresh_failures": refresh_failures,
+            "report": report_name,
+        }));
+    }
 
-    println!("Migrated {} mints", completed_mints.len());
-    println!("Failed to migrate {} mints", errors.len());
+    say!("Migrated {} mints", completed_mints.len());
+    say!(
+        "Skipped {} already migrated mints",
+        already_migrated_mints.len()
+    );
+    say!("Skipped {} ineligible mints", ineligible_mints.len());
+    say!("Failed to migrate {} mints", errors.len());
+    if let Some(limiter) = &limiter {
+        say!("Rate limit: {}", limiter.stats());
+    }
+    print_refresh_failures(&refresh_failures);
 
     let success_name = format!("{collection_mint}_migrated_mints.json");
+    let already_migrated_name = format!("{collection_mint}_already_migrated_mints.json");
+    let ineligible_name = format!("{collection_mint}_ineligible_mints.json");
     let failures_name = format!("{collection_mint}_failed_mints.json");
-    let f = File::create(success_name)?;
-    let e = File::create(failures_name)?;
+    let f = File::create(&success_name)?;
+    let a = File::create(&already_migrated_name)?;
+    let i = File::create(&ineligible_name)?;
+    let e = File::create(&failures_name)?;
     serde_json::to_writer_pretty(f, &completed_mints)?;
+    serde_json::to_writer_pretty(a, &already_migrated_mints)?;
+    serde_json::to_writer_pretty(i, &ineligible_mints)?;
     serde_json::to_writer_pretty(e, &errors)?;
 
+    Ok(json!({
+        "migrated": completed_mints,
+        "already_migrated": already_migrated_mints.len(),
+        "ineligible": ineligible_mints.len(),
+        "failed": errors.len(),
+        "rate_limit": limiter.as_ref().map(|limiter| rate_limit_json(limiter.stats())),
+        "blockhash_refresh_failures": refresh_failures,
+        "files": {
+            "migrated": success_name,
+            "already_migrated": already_migrated_name,
+            "ineligible": ineligible_name,
+            "failed": failures_name,
+        },
+    }))
+}
+
+/// How often to check the cluster clock while waiting for the unlock time.
+const UNLOCK_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Polls the cluster clock until a timed migration unlocks, counting down in
+/// between polls. Transient RPC errors are ridden out.
+async fn wait_until_unlocked(client: &AsyncRpcClient, state: &MigrationState) -> Result<()> {
+    if let UnlockMethod::Vote = state.unlock_method {
+        bail!("--wait-for-unlock only works for migrations with a timed unlock");
+    }
+
+    let unlock_time = state.status.unlock_time;
+    let spinner = spinner_with_style();
+
+    // Cluster time as of the last successful poll, and when that was.
+    let mut last_poll: Option<(i64, Instant)> = None;
+
+    loop {
+        let stale = match last_poll {
+            Some((_, polled)) => polled.elapsed() >= UNLOCK_POLL_INTERVAL,
+            None => true,
+        };
+
+        if stale {
+            match get_cluster_time_async(client).await {
+                Ok(cluster_time) => last_poll = Some((cluster_time, Instant::now())),
+                Err(e) if classify(&e) == ErrorClass::Transient => {
+                    spinner.set_message(format!("Failed to fetch cluster time, retrying: {e}"));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some((cluster_time, polled)) = last_poll {
+            let now = cluster_time + polled.elapsed().as_secs() as i64;
+            if now >= unlock_time {
+                break;
+            }
+
+            spinner.set_message(format!(
+                "Waiting for unlock at {}: {}",
+                format_timestamp(unlock_time),
+                format_countdown(unlock_time - now)
+            ));
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    spinner.finish_and_clear();
+    say!("{}", style("Migration unlocked").green());
+
     Ok(())
 }
 
+/// An item's share of the `--batch-size` limit. The spawn loop acquires it up
+/// front so no more than that many items are in flight, and batch mode releases
+/// it while a sent transaction waits to be confirmed.
+#[derive(Clone)]
+struct ItemPermit {
+    semaphore: Arc<Semaphore>,
+    held: Arc<Mutex<Option<OwnedSemaphorePermit>>>,
+}
+
+impl ItemPermit {
+    fn new(semaphore: Arc<Semaphore>, permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            semaphore,
+            held: Arc::new(Mutex::new(Some(permit))),
+        }
+    }
+
+    /// Takes the permit back when retrying after an attempt released it.
+    async fn reacquire(&self) -> Result<()> {
+        let mut held = self.held.lock().await;
+        if held.is_none() {
+            *held = Some(self.semaphore.clone().acquire_owned().await?);
+        }
+
+        Ok(())
+    }
+
+    async fn release(&self) {
+        self.held.lock().await.take();
+    }
+}
+
+#[derive(Clone)]
 struct MigrateArgs {
-    keypair: Arc<Keypair>,
-    client: Arc<RpcClient>,
+    payer: Arc<Keypair>,
+    client: Arc<AsyncRpcClient>,
+    blockhash: BlockhashCache,
+    permit: ItemPermit,
+    confirmations: Option<ConfirmationQueue>,
+    compute_budget: ComputeBudget,
+    dry_run: bool,
     collection_mint: Pubkey,
     item_mint: Pubkey,
     rule_set: Pubkey,
+    authority: Pubkey,
 }
 
-async fn migrate_mint(args: MigrateArgs) -> Result<Signature> {
-    let item_token = get_nft_token_account(&args.client, args.item_mint)?;
-
-    let account = args.client.get_account(&item_token)?;
+enum MigrateOutcome {
+    Migrated(Signature),
+    AlreadyMigrated,
+    Ineligible(Vec<String>),
+    Simulated(Simulation),
+}
 
-    let token_account = TokenAccount::unpack(&account.data)?;
+fn ineligible(reasons: Vec<Ineligibility>) -> MigrateOutcome {
+    MigrateOutcome::Ineligible(reasons.iter().map(ToString::to_string).collect())
+}
 
-    let token_owner = token_account.owner;
-    let token_owner_program = args.client.get_account(&token_owner)?.owner;
+async fn migrate_mint(args: MigrateArgs) -> Result<MigrateOutcome> {
+    args.permit.reacquire().await?;
 
-    let token_owner_program_account = args.client.get_account(&token_owner_program)?;
+    // Items that are already pNFTs would just fail in the program, so don't
+    // spend a transaction on them.
+    let metadata = get_metadata_async(&args.client, &args.item_mint).await?;
+    if is_programmable(&metadata) {
+        return Ok(MigrateOutcome::AlreadyMigrated);
+    }
 
-    // We need to pass the program data buffer to the migration program
-    // if the token owner program is an upgradeable program.
-    let state_opt: Option<UpgradeableLoaderState> =
-        bincode::deserialize(&token_owner_program_account.data).ok();
+    let mut reasons = check_metadata(&metadata, &args.collection_mint, &args.authority);
 
-    let token_owner_program_buffer = if let Some(state) = state_opt {
-        match state {
-            UpgradeableLoaderState::Program {
-                programdata_address,
-            } => Some(programdata_address),
-            _ => None,
+    // Burned mints and missing or shared token accounts are ineligible rather
+    // than failures; anything else is an RPC error worth retrying.
+    let item_token = match get_nft_token_account_async(&args.client, args.item_mint).await {
+        Ok(item_token) => item_token,
+        Err(e) => {
+            reasons.push(e.downcast::<TokenAccountError>()?.into());
+            return Ok(ineligible(reasons));
         }
-    } else {
-        None
     };
 
-    let params = MigrateParams {
+    let account = args.client.get_account(&item_token).await?;
+
+    let token_account = TokenAccount::unpack(&account.data)?;
+
+    reasons.extend(check_token_account(&token_account));
+    if !reasons.is_empty() {
+        return Ok(ineligible(reasons));
+    }
+
+    let token_owner = token_account.owner;
+    let (token_owner_program, token_owner_program_buffer) =
+        get_token_owner_program_async(&args.client, &token_owner).await?;
+
+    let latest_blockhash = args.blockhash.get();
+
+    let params = MigrateAsyncParams {
         client: &args.client,
-        payer: &args.keypair,
+        payer: &args.payer,
         item_mint: args.item_mint,
         item_token,
         token_owner,
@@ -491,31 +1112,166 @@ async fn migrate_mint(args: MigrateArgs) -> Result<Signature> {
         token_owner_program_buffer,
         collection_mint: args.collection_mint,
         rule_set: args.rule_set,
+        recent_blockhash: Some(latest_blockhash.hash),
+        compute_budget: args.compute_budget,
+        nonce: None,
+    };
+
+    if args.dry_run {
+        let transaction = migrate_item_transaction(params).await?;
+        let result = args.client.simulate_transaction(&transaction).await?.value;
+
+        return Ok(MigrateOutcome::Simulated(result.into()));
+    }
+
+    let confirmations = match &args.confirmations {
+        Some(confirmations) => confirmations,
+        None => {
+            return migrate_item_async(params)
+                .await
+                .map(MigrateOutcome::Migrated)
+        }
+    };
+
+    let transaction = migrate_item_transaction(params).await?;
+    let sig = args.client.send_transaction(&transaction).await?;
+
+    // Free up the slot for the next item while this one waits to be confirmed.
+    args.permit.release().await;
+
+    confirmations
+        .confirm(sig, transaction, latest_blockhash.last_valid_block_height)
+        .await?;
+
+    Ok(MigrateOutcome::Migrated(sig))
+}
+
+pub fn process_validate_list(
+    mint_list: PathBuf,
+    mint_column: Option<String>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mint_list = load_mint_list(&mint_list, mint_column.as_deref())?;
+    say!(
+        "Loaded {} entries from {} mint list",
+        mint_list.entries.len(),
+        mint_list.format
+    );
+
+    let report = mint_list.validate();
+    print_validation_report(&report);
+
+    if let Some(output) = &output {
+        let mints: Vec<String> = report.mints.iter().map(ToString::to_string).collect();
+        let f = File::create(output)?;
+        serde_json::to_writer_pretty(f, &mints)?;
+        say!("Wrote {} valid mints to {}", mints.len(), output.display());
+    }
+
+    emit(&json!({
+        "format": mint_list.format.to_string(),
+        "entries": mint_list.entries.len(),
+        "valid": report.mints.len(),
+        "invalid": report.invalid,
+        "duplicates": report.duplicates,
+        "output": output,
+    }))?;
+
+    if !report.invalid.is_empty() {
+        bail!("Mint list has {} invalid entries", report.invalid.len());
+    }
+
+    say!("{}", style("Mint list is valid").green());
+
+    Ok(())
+}
+
+pub fn process_snapshot(
+    options: &GlobalOptions,
+    collection_mint: Pubkey,
+    first_creator: Option<Pubkey>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let config = CliConfig::new(options)?;
+
+    if first_creator.is_none() {
+        let warning = "No first creator given, scanning every token metadata account. \
+            This can take a very long time.";
+        say!("{}", style(warning).yellow());
+    }
+
+    let params = SnapshotParams {
+        client: &config.client,
+        collection_mint,
+        first_creator,
     };
+    let spinner = spinner_with_style();
+    spinner.set_message("Fetching collection items...");
+    let mints = snapshot(params)?;
+    spinner.finish();
 
-    migrate_item(params)
+    let mints: Vec<String> = mints.iter().map(ToString::to_string).collect();
+
+    let file_name =
+        output.unwrap_or_else(|| PathBuf::from(format!("{collection_mint}_mint_list.json")));
+    let f = File::create(&file_name)?;
+    serde_json::to_writer_pretty(f, &mints)?;
+
+    say!(
+        "{}",
+        style(format!(
+            "Wrote {} mints to {}",
+            mints.len(),
+            file_name.display()
+        ))
+        .green()
+    );
+
+    emit(&json!({
+        "count": mints.len(),
+        "file": file_name,
+    }))
 }
 
 pub async fn process_check(
-    keypair: Option<PathBuf>,
-    rpc_url: Option<String>,
+    options: &GlobalOptions,
     mint_list: PathBuf,
+    mint_column: Option<String>,
+    skip_invalid: bool,
     batch_size: usize,
+    max_rps: Option<u32>,
 ) -> Result<()> {
-    let config = setup::CliConfig::new(keypair, rpc_url)?;
+    let summary = check_mints(
+        options,
+        mint_list,
+        mint_column,
+        skip_invalid,
+        batch_size,
+        max_rps,
+    )
+    .await?;
+
+    emit(&summary)
+}
 
-    let f = File::open(mint_list)?;
-    let mints: Vec<String> = serde_json::from_reader(f)?;
-    let mints: Vec<Pubkey> = mints
-        .into_iter()
-        .map(|s| Pubkey::from_str(&s).unwrap())
-        .collect();
+async fn check_mints(
+    options: &GlobalOptions,
+    mint_list: PathBuf,
+    mint_column: Option<String>,
+    skip_invalid: bool,
+    batch_size: usize,
+    max_rps: Option<u32>,
+) -> Result<Value> {
+    let config = CliConfig::new(options)?;
+
+    let mints = load_mints(&mint_list, mint_column.as_deref(), skip_invalid)?;
 
     let completed_mints: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
     let unmigrated_mints: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
     let errors: Arc<Mutex<Vec<MigrationError>>> = Arc::new(Mutex::new(Vec::new()));
 
-    let client = Arc::new(config.client);
+    let limiter = max_rps.map(|rps| Arc::new(RateLimiter::new(rps)));
+    let client = Arc::new(config.limited_async_client(limiter.clone()));
 
     let mut tasks = FuturesUnordered::new();
     let semaphore = Arc::new(Semaphore::new(batch_size));
@@ -533,38 +1289,22 @@ pub async fn process_check(
         tasks.push(tokio::spawn(async move {
             let _permit = permit;
 
-            let (metadata, _) = find_metadata_account(&item_mint);
-
-            let account = match client.get_account_data(&metadata) {
-                Ok(account) => account,
-                Err(e) => {
-                    errors.lock().await.push(MigrationError {
-                        mint: item_mint.to_string(),
-                        error: e.to_string(),
-                    });
-                    pb.inc(1);
-                    return;
-                }
-            };
-
-            let md = match Metadata::safe_deserialize(&account) {
+            let md = match get_metadata_async(&client, &item_mint).await {
                 Ok(md) => md,
                 Err(e) => {
                     errors.lock().await.push(MigrationError {
                         mint: item_mint.to_string(),
                         error: e.to_string(),
+                        attempts: 1,
+                        class: classify(&e),
                     });
                     pb.inc(1);
                     return;
                 }
             };
 
-            if let Some(token_standard) = md.token_standard {
-                if token_standard == TokenStandard::ProgrammableNonFungible {
-                    completed_mints.lock().await.push(item_mint.to_string());
-                } else {
-                    unmigrated_mints.lock().await.push(item_mint.to_string());
-                }
+            if is_programmable(&md) {
+                completed_mints.lock().await.push(item_mint.to_string());
             } else {
                 unmigrated_mints.lock().await.push(item_mint.to_string());
             }
@@ -581,21 +1321,564 @@ pub async fn process_check(
     let unmigrated_mints = Arc::try_unwrap(unmigrated_mints).unwrap().into_inner();
     let errors = Arc::try_unwrap(errors).unwrap().into_inner();
 
-    println!("Migrated {} mints", completed_mints.len());
-    println!("Unmigrated {} mints", unmigrated_mints.len());
-    println!("Encountered {} errors", errors.len());
+    say!("Migrated {} mints", completed_mints.len());
+    say!("Unmigrated {} mints", unmigrated_mints.len());
+    say!("Encountered {} errors", errors.len());
+    if let Some(limiter) = &limiter {
+        say!("Rate limit: {}", limiter.stats());
+    }
 
     let migrated_name = "migrated_mints.json".to_string();
     let unmigrated_name = "unmigrated_mints.json".to_string();
     let errors_name = "errors.json".to_string();
 
-    let m = File::create(migrated_name)?;
-    let u = File::create(unmigrated_name)?;
-    let e = File::create(errors_name)?;
+    let m = File::create(&migrated_name)?;
+    let u = File::create(&unmigrated_name)?;
+    let e = File::create(&errors_name)?;
 
     serde_json::to_writer_pretty(m, &completed_mints)?;
     serde_json::to_writer_pretty(u, &unmigrated_mints)?;
     serde_json::to_writer_pretty(e, &errors)?;
 
-    Ok(())
+    Ok(json!({
+        "migrated": completed_mints.len(),
+        "unmigrated": unmigrated_mints.len(),
+        "errors": errors.len(),
+        "rate_limit": limiter.as_ref().map(|limiter| rate_limit_json(limiter.stats())),
+        "files": {
+            "migrated": migrated_name,
+            "unmigrated": unmigrated_name,
+            "errors": errors_name,
+        },
+    }))
+}
+
+/// Items held by a single token owner program.
+#[derive(Serialize, Debug, Default)]
+pub struct ProgramHoldings {
+    programdata: Option<String>,
+    items: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct OwnersReport {
+    wallets: Vec<String>,
+    programs: BTreeMap<String, ProgramHoldings>,
+    errors: Vec<MigrationError>,
+}
+
+async fn get_item_owner_program(
+    client: &AsyncRpcClient,
+    item_mint: Pubkey,
+) -> Result<(Pubkey, Option<Pubkey>)> {
+    let item_token = get_nft_token_account_async(client, item_mint).await?;
+    let account = client.get_account(&item_token).await?;
+    let token_account = TokenAccount::unpack(&account.data)?;
+
+    get_token_owner_program_async(client, &token_account.owner).await
+}
+
+pub async fn process_owners(
+    options: &GlobalOptions,
+    mint_list: PathBuf,
+    mint_column: Option<String>,
+    skip_invalid: bool,
+    batch_size: usize,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let config = CliConfig::new(options)?;
+
+    let mints = load_mints(&mint_list, mint_column.as_deref(), skip_invalid)?;
+
+    let report: Arc<Mutex<OwnersReport>> = Arc::new(Mutex::new(OwnersReport::default()));
+
+    let client = Arc::new(config.async_client());
+
+    let mut tasks = FuturesUnordered::new();
+    let semaphore = Arc::new(Semaphore::new(batch_size));
+    let pb = create_progress_bar("", mints.len() as u64);
+
+    pb.set_message("Resolving token owners...");
+    for item_mint in mints {
+        let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
+        let pb = pb.clone();
+        let report = report.clone();
+        let client = client.clone();
+
+        tasks.push(tokio::spawn(async move {
+            let _permit = permit;
+
+            let result = get_item_owner_program(&client, item_mint).await;
+
+            let mut report = report.lock().await;
+            match result {
+                Ok((program, _)) if program == system_program::ID => {
+                    report.wallets.push(item_mint.to_string())
+                }
+                Ok((program, programdata)) => {
+                    let holdings = report.programs.entry(program.to_string()).or_default();
+                    holdings.programdata = programdata.map(|p| p.to_string());
+                    holdings.items.push(item_mint.to_string());
+                }
+                Err(e) => report.errors.push(MigrationError {
+                    mint: item_mint.to_string(),
+                    error: e.to_string(),
+                    attempts: 1,
+                    class: classify(&e),
+                }),
+            }
+
+            pb.inc(1);
+        }));
+    }
+
+    while let Some(task) = tasks.next().await {
+        task?;
+    }
+    pb.finish_and_clear();
+
+    let report = Arc::try_unwrap(report).unwrap().into_inner();
+
+    say!("{} items held in wallets", report.wallets.len());
+
+    let mut programs: Vec<(&String, &ProgramHoldings)> = report.programs.iter().collect();
+    programs.sort_by(|a, b| b.1.items.len().cmp(&a.1.items.len()));
+
+    for (program, holdings) in programs {
+        let programdata = match &holdings.programdata {
+            Some(programdata) => format!(" (program data {programdata})"),
+            None => String::new(),
+        };
+        say!(
+            "{} items held by program {}{}",
+            holdings.items.len(),
+            style(program).cyan(),
+            programdata
+        );
+    }
+
+    say!("Encountered {} errors", report.errors.len());
+
+    let output = output.unwrap_or_else(|| PathBuf::from("owners.json"));
+    let f = File::create(&output)?;
+    serde_json::to_writer_pretty(f, &report)?;
+    say!("Wrote owner report to {}", output.display());
+
+    let programs: serde_json::Map<String, Value> = report
+        .programs
+        .iter()
+        .map(|(program, holdings)| (program.clone(), json!(holdings.items.len())))
+        .collect();
+
+    emit(&json!({
+        "wallets": report.wallets.len(),
+        "programs": programs,
+        "errors": report.errors.len(),
+        "report": output,
+    }))
+}
+
+fn step_status(outcome: &TxOutcome) -> StepStatus {
+    match outcome {
+        TxOutcome::Sent(_) => StepStatus::Completed,
+        TxOutcome::Simulated(_) => StepStatus::Simulated,
+    }
+}
+
+fn account_exists(client: &RpcClient, address: &Pubkey) -> Result<bool> {
+    Ok(client
+        .get_account_with_commitment(address, client.commitment())?
+        .value
+        .is_some())
+}
+
+fn init_signer_step(config: &CliConfig) -> Result<(StepStatus, Value)> {
+    if account_exists(&config.client, &PROGRAM_SIGNER)? {
+        return Ok((
+            StepStatus::Skipped,
+            json!({ "signer": PROGRAM_SIGNER.to_string() }),
+        ));
+    }
+
+    let outcome = send_init_signer(config)?;
+    let transaction = print_tx_outcome(&config.client, &outcome, "Initialized program signer")?;
+
+    Ok((step_status(&outcome), json!({ "transaction": transaction })))
+}
+
+fn initialize_step(
+    config: &CliConfig,
+    collection_mint: Pubkey,
+    unlock_method: Option<UnlockMethod>,
+    collection_size: Option<u32>,
+) -> Result<(StepStatus, Value)> {
+    let (migration_state, _) = find_migration_state_pda(&collection_mint);
+
+    if account_exists(&config.client, &migration_state)? {
+        return Ok((
+            StepStatus::Skipped,
+            json!({ "address": migration_state.to_string() }),
+        ));
+    }
+
+    let (unlock_method, collection_size) = match (unlock_method, collection_size) {
+        (Some(unlock_method), Some(size)) => (unlock_method, size),
+        _ => {
+            bail!("No migration state exists yet, pass --unlock-method and --size to initialize it")
+        }
+    };
+
+    let outcome = initialize(InitializeParams {
+        client: &config.client,
+        payer: config.payer(),
+        authority: &config.keypair,
+        rule_set: None,
+        collection_mint,
+        unlock_method,
+        collection_size,
+        recent_blockhash: None,
+        compute_budget: config.compute_budget,
+        nonce: None,
+        multisig: None,
+        dry_run: config.dry_run,
+    })?;
+    let transaction = print_tx_outcome(&config.client, &outcome, "Intialized migration state")?;
+
+    Ok((step_status(&outcome), json!({ "transaction": transaction })))
+}
+
+/// Whether the migration has been started. Starting sets the unlock time, so a
+/// state with one is past it, as is one that already unlocked or is migrating.
+fn is_started(state: &MigrationState) -> bool {
+    let status = &state.status;
+
+    status.unlock_time != 0 || !status.is_locked || status.in_progress
+}
+
+fn start_step(config: &CliConfig, collection_mint: Pubkey) -> Result<(StepStatus, Value)> {
+    let state = get_state(GetStateParams {
+        client: &config.client,
+        collection_mint,
+    })?;
+
+    if is_started(&state) {
+        return Ok((StepStatus::Skipped, json!({})));
+    }
+
+    let outcome = start(StartParams {
+        client: &config.client,
+        payer: config.payer(),
+        authority: &config.keypair,
+        collection_mint,
+        recent_blockhash: None,
+        compute_budget: config.compute_budget,
+        nonce: None,
+        multisig: None,
+        dry_run: config.dry_run,
+    })?;
+    let transaction = print_tx_outcome(&config.client, &outcome, "Started migration")?;
+
+    Ok((step_status(&outcome), json!({ "transaction": transaction })))
+}
+
+async fn wait_for_unlock_step(
+    config: &CliConfig,
+    collection_mint: Pubkey,
+) -> Result<(StepStatus, Value)> {
+    let client = config.async_client();
+
+    let state = get_state_async(GetStateAsyncParams {
+        client: &client,
+        collection_mint,
+    })
+    .await?;
+
+    if let UnlockMethod::Vote = state.unlock_method {
+        if state.status.is_locked {
+            bail!("Migration is waiting on a vote to unlock");
+        }
+        return Ok((StepStatus::Skipped, json!({ "unlock_method": "vote" })));
+    }
+
+    let unlock_time = json!({
+        "unlock_time": state.status.unlock_time,
+        "unlock_time_utc": format_timestamp(state.status.unlock_time),
+    });
+
+    if get_cluster_time_async(&client).await? >= state.status.unlock_time {
+        return Ok((StepStatus::Skipped, unlock_time));
+    }
+
+    wait_until_unlocked(&client, &state).await?;
+
+    Ok((StepStatus::Completed, unlock_time))
+}
+
+/// Works through the migration lifecycle for a collection, skipping the steps
+/// that are already done on chain, and records every step in a run report.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_run(
+    options: &GlobalOptions,
+    collection_mint: Pubkey,
+    mint_list: PathBuf,
+    mint_column: Option<String>,
+    skip_invalid: bool,
+    unlock_method: Option<UnlockMethodArg>,
+    collection_size: Option<u32>,
+    batch_size: usize,
+    retry_policy: RetryPolicy,
+    send_mode: SendMode,
+    max_rps: Option<u32>,
+) -> Result<()> {
+    reject_nonce(options)?;
+    if options.multisig.is_some() {
+        bail!("Run can't wait for multisig approvals, propose init and start separately");
+    }
+
+    let config = CliConfig::new(options)?;
+    let mut report = RunReport::new(&collection_mint);
+
+    let mut status = report.step(RunStep::InitSigner, Utc::now(), init_signer_step(&config))?;
+
+    if status != StepStatus::Simulated {
+        status = report.step(
+            RunStep::Initialize,
+            Utc::now(),
+            initialize_step(
+                &config,
+                collection_mint,
+                unlock_method.map(UnlockMethod::from),
+                collection_size,
+            ),
+        )?;
+    }
+
+    if status != StepStatus::Simulated {
+        status = report.step(
+            RunStep::Start,
+            Utc::now(),
+            start_step(&config, collection_mint),
+        )?;
+    }
+
+    // Later steps depend on the on-chain changes a dry run only simulated.
+    if status != StepStatus::Simulated {
+        let started_at = Utc::now();
+        let result = wait_for_unlock_step(&config, collection_mint).await;
+        report.step(RunStep::WaitForUnlock, started_at, result)?;
+
+        // Resume from the journal so rerunning after an interruption picks up
+        // where the last run stopped.
+        let started_at = Utc::now();
+        let result = migrate_collection(
+            options,
+            collection_mint,
+            mint_list.clone(),
+            mint_column.clone(),
+            skip_invalid,
+            batch_size,
+            true,
+            retry_policy,
+            send_mode,
+            max_rps,
+            false,
+        )
+        .await
+        .map(|summary| (StepStatus::Completed, summary));
+        report.step(RunStep::Migrate, started_at, result)?;
+
+        let started_at = Utc::now();
+        let result = check_mints(
+            options,
+            mint_list,
+            mint_column,
+            skip_invalid,
+            batch_size,
+            max_rps,
+        )
+        .await
+        .map(|summary| (StepStatus::Completed, summary));
+        report.step(RunStep::Check, started_at, result)?;
+    }
+
+    report.finish()?;
+    say!("Wrote run report to {}", report.path().display());
+
+    emit(&serde_json::to_value(&report)?)
+}
+
+pub fn process_sign_msg(
+    options: &GlobalOptions,
+    message: String,
+    blockhash: Option<Hash>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let keypair = load_keypair(options)?;
+    let message = read_message(&message)?;
+
+    say!("Fee payer: {}", message.account_keys[0]);
+    say!("Signer:    {}", style(keypair.pubkey()).cyan());
+
+    let signed = sign_message(message, &keypair, blockhash)?;
+
+    let output =
+        output.unwrap_or_else(|| PathBuf::from(format!("{}_signature.json", keypair.pubkey())));
+    let f = File::create(&output)?;
+    serde_json::to_writer_pretty(f, &signed)?;
+
+    say!(
+        "{}",
+        style(format!("Wrote signature to {}", output.display())).green()
+    );
+
+    emit(&json!({
+        "signer": keypair.pubkey().to_string(),
+        "message": signed.message,
+        "file": output,
+    }))
+}
+
+pub fn process_submit(options: &GlobalOptions, signature_files: Vec<PathBuf>) -> Result<()> {
+    // The transaction is already fully signed, so no keypair is needed here.
+    let client = rpc_client(options)?;
+
+    let signed = signature_files
+        .iter()
+        .map(|path| Ok(serde_json::from_reader(File::open(path)?)?))
+        .collect::<Result<Vec<SignedMessage>>>()?;
+
+    let transaction = assemble_transaction(&signed)?;
+    say!(
+        "Collected all {} required signatures",
+        transaction.signatures.len()
+    );
+
+    let spinner = spinner_with_style();
+    spinner.set_message("Submitting transaction...");
+    let outcome = send_or_simulate(&client, &transaction, options.dry_run)?;
+    spinner.finish();
+
+    let transaction = print_tx_outcome(&client, &outcome, "Submitted transaction")?;
+
+    emit(&json!({ "transaction": transaction }))
+}
+
+pub fn process_inspect(input: String) -> Result<()> {
+    let inspected = decode_serialized(&input)?;
+    let message = &inspected.message;
+
+    match &inspected.signatures {
+        Some(signatures) => {
+            let signed = signatures
+                .iter()
+                .filter(|signature| **signature != Signature::default())
+                .count();
+            say!(
+                "Transaction with {signed} of {} signatures",
+                signatures.len()
+            );
+        }
+        None => say!("Unsigned message"),
+    }
+    say!("Recent blockhash: {}", message.recent_blockhash);
+
+    let accounts = summarize_accounts(message);
+    say!("\nAccounts:");
+    for account in &accounts {
+        let flags = match (account.signer, account.writable) {
+            (true, true) => "signer writable",
+            (true, false) => "signer readonly",
+            (false, true) => "writable",
+            (false, false) => "readonly",
+        };
+        say!(
+            "  [{}] {} {:<16} {}",
+            account.index,
+            account.pubkey,
+            flags,
+            style(account.labels.join(", ")).cyan()
+        );
+    }
+
+    let instructions = summarize_instructions(message);
+    say!("\nInstructions:");
+    for (i, instruction) in instructions.iter().enumerate() {
+        say!(
+            "  #{i} {}",
+            style(
+                instruction
+                    .program_name
+                    .unwrap_or(instruction.program.as_str())
+            )
+            .green()
+        );
+        say!("     Accounts: {:?}", instruction.accounts);
+        match &instruction.decoded {
+            Some(decoded) => say!("     Data: {}", style(decoded).yellow()),
+            None => say!("     Data (bs58): {}", instruction.data),
+        }
+    }
+
+    emit(&json!({
+        "signatures": inspected
+            .signatures
+            .map(|signatures| signatures.iter().map(|s| s.to_string()).collect::<Vec<_>>()),
+        "recent_blockhash": message.recent_blockhash.to_string(),
+        "accounts": accounts,
+        "instructions": instructions,
+    }))
+}
+
+pub fn process_create_nonce(
+    options: &GlobalOptions,
+    nonce_keypair: Option<PathBuf>,
+    authority: Option<Pubkey>,
+) -> Result<()> {
+    let config = CliConfig::new(options)?;
+
+    let generated = nonce_keypair.is_none();
+    let nonce_account = match nonce_keypair {
+        Some(path) => read_keypair_file(&path)
+            .map_err(|_| anyhow!("Unable to read nonce keypair file {}", path.display()))?,
+        None => Keypair::new(),
+    };
+    let authority = authority.unwrap_or_else(|| config.payer().pubkey());
+
+    // Save a generated keypair before sending, so it isn't lost if anything
+    // fails once the account exists.
+    let keypair_file = if generated && !config.dry_run {
+        let path = PathBuf::from(format!("{}_nonce.json", nonce_account.pubkey()));
+        write_keypair_file(&nonce_account, &path)
+            .map_err(|e| anyhow!("Unable to write nonce keypair file: {e}"))?;
+        say!("Wrote nonce keypair to {}", path.display());
+        Some(path)
+    } else {
+        None
+    };
+
+    say!("Nonce account: {}", style(nonce_account.pubkey()).green());
+    say!("Nonce authority: {authority}");
+
+    let params = CreateNonceParams {
+        client: &config.client,
+        payer: config.payer(),
+        nonce_account: &nonce_account,
+        authority,
+        recent_blockhash: Some(config.recent_blockhash),
+        compute_budget: config.compute_budget,
+        dry_run: config.dry_run,
+    };
+    let spinner = spinner_with_style();
+    spinner.set_message("Creating nonce account...");
+    let outcome = create_nonce_account(params)?;
+    spinner.finish();
+
+    let transaction = print_tx_outcome(&config.client, &outcome, "Created nonce account")?;
+
+    emit(&json!({
+        "transaction": transaction,
+        "nonce_account": nonce_account.pubkey().to_string(),
+        "authority": authority.to_string(),
+        "keypair_file": keypair_file,
+    }))
 }
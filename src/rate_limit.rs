@@ -0,0 +1,97 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket limiting the requests per second sent to an RPC server. Bursts
+/// of up to one second's worth of requests are let through immediately.
+pub struct RateLimiter {
+    rate: f64,
+    bucket: Mutex<Bucket>,
+    requests: AtomicU64,
+    throttled: AtomicU64,
+    waited_micros: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitStats {
+    pub requests: u64,
+    pub throttled: u64,
+    pub waited: Duration,
+}
+
+impl fmt::Display for RateLimitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} RPC requests, {} throttled for {:.1}s in total",
+            self.requests,
+            self.throttled,
+            self.waited.as_secs_f64()
+        )
+    }
+}
+
+impl RateLimiter {
+    pub fn new(max_rps: u32) -> Self {
+        let rate = f64::from(max_rps);
+
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate,
+                last_refill: Instant::now(),
+            }),
+            requests: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+            waited_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Takes a token, waiting until one is available.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+
+            bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+            bucket.last_refill = now;
+            bucket.tokens -= 1.0;
+
+            // A negative balance reserves a token that hasn't refilled yet, so
+            // concurrent callers queue up behind each other.
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / self.rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        self.requests.fetch_add(1, Ordering::Relaxed);
+
+        if !wait.is_zero() {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            self.waited_micros
+                .fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            waited: Duration::from_micros(self.waited_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::transaction::TransactionError;
+
+// JSON RPC error codes for a node that is unhealthy or behind the cluster.
+const BLOCK_NOT_AVAILABLE: i64 = -32004;
+const NODE_UNHEALTHY: i64 = -32005;
+const MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter for the given (1-based) attempt.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let cap = exp.min(self.max_delay);
+
+        Duration::from_millis(rand::thread_rng().gen_range(0..=cap.as_millis() as u64))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// Worth retrying: expired blockhashes, rate limits, timeouts, lagging nodes.
+    Transient,
+    /// Retrying won't help, e.g. program errors or malformed accounts.
+    #[default]
+    Permanent,
+}
+
+pub struct RetryError {
+    pub error: anyhow::Error,
+    pub attempts: u32,
+    pub class: ErrorClass,
+}
+
+/// Runs `f` until it succeeds, fails with a permanent error, or the policy's
+/// attempts are exhausted.
+pub async fn with_retry<T, F, Fut>(policy: &RetryPolicy, mut f: F) -> Result<T, RetryError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(error) => {
+                let class = classify(&error);
+
+                if class == ErrorClass::Permanent || attempt >= policy.max_attempts {
+                    return Err(RetryError {
+                        error,
+                        attempts: attempt,
+                        class,
+                    });
+                }
+
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+pub fn classify(error: &anyhow::Error) -> ErrorClass {
+    match error.downcast_ref::<ClientError>() {
+        Some(client_error) => classify_client_error(client_error.kind()),
+        None => classify_message(&error.to_string()),
+    }
+}
+
+pub fn classify_client_error(kind: &ClientErrorKind) -> ErrorClass {
+    match kind {
+        ClientErrorKind::Io(_) => ErrorClass::Transient,
+        ClientErrorKind::Reqwest(e) => match e.status() {
+            // Other 4xx responses mean the request itself is bad.
+            Some(status) if status.is_client_error() && status.as_u16() != 429 => {
+                ErrorClass::Permanent
+            }
+            _ => ErrorClass::Transient,
+        },
+        ClientErrorKind::TransactionError(e) => classify_transaction_error(e),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, data, .. }) => match data {
+            RpcResponseErrorData::SendTransactionPreflightFailure(result) => match &result.err {
+                Some(e) => classify_transaction_error(e),
+                None => ErrorClass::Transient,
+            },
+            RpcResponseErrorData::NodeUnhealthy { .. } => ErrorClass::Transient,
+            RpcResponseErrorData::Empty => match *code {
+                BLOCK_NOT_AVAILABLE | NODE_UNHEALTHY | MIN_CONTEXT_SLOT_NOT_REACHED => {
+                    ErrorClass::Transient
+                }
+                _ => classify_message(&kind.to_string()),
+            },
+        },
+        _ => classify_message(&kind.to_string()),
+    }
+}
+
+fn classify_transaction_error(error: &TransactionError) -> ErrorClass {
+    match error {
+        TransactionError::BlockhashNotFound | TransactionError::AccountInUse => {
+            ErrorClass::Transient
+        }
+        _ => ErrorClass::Permanent,
+    }
+}
+
+fn classify_message(message: &str) -> ErrorClass {
+    const TRANSIENT_PATTERNS: &[&str] = &[
+        "blockhash not found",
+        "block height exceeded",
+        "unable to confirm transaction",
+        "429",
+        "too many requests",
+        "timed out",
+        "timeout",
+        "node is behind",
+        "node is unhealthy",
+        "connection reset",
+        "connection refused",
+    ];
+
+    let message = message.to_lowercase();
+
+    if TRANSIENT_PATTERNS.iter().any(|p| message.contains(p)) {
+        ErrorClass::Transient
+    } else {
+        ErrorClass::Permanent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use solana_sdk::instruction::InstructionError;
+
+    use super::*;
+
+    fn rpc_error(code: i64, message: &str) -> anyhow::Error {
+        ClientError::from(ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code,
+            message: message.to_string(),
+            data: RpcResponseErrorData::Empty,
+        }))
+        .into()
+    }
+
+    #[test]
+    fn expired_blockhash_is_transient() {
+        let error = ClientError::from(TransactionError::BlockhashNotFound);
+
+        assert_eq!(classify(&error.into()), ErrorClass::Transient);
+    }
+
+    #[test]
+    fn program_errors_are_permanent() {
+        let error = ClientError::from(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(1),
+        ));
+
+        assert_eq!(classify(&error.into()), ErrorClass::Permanent);
+    }
+
+    #[test]
+    fn lagging_node_codes_are_transient() {
+        for code in [
+            BLOCK_NOT_AVAILABLE,
+            NODE_UNHEALTHY,
+            MIN_CONTEXT_SLOT_NOT_REACHED,
+        ] {
+            assert_eq!(
+                classify(&rpc_error(code, "Node is lagging")),
+                ErrorClass::Transient
+            );
+        }
+    }
+
+    #[test]
+    fn other_rpc_codes_are_classified_by_message() {
+        assert_eq!(
+            classify(&rpc_error(-32000, "Too many requests")),
+            ErrorClass::Transient
+        );
+        assert_eq!(
+            classify(&rpc_error(-32602, "Invalid params")),
+            ErrorClass::Permanent
+        );
+    }
+
+    #[test]
+    fn plain_errors_are_classified_by_message() {
+        assert_eq!(
+            classify(&anyhow!("operation timed out")),
+            ErrorClass::Transient
+        );
+        assert_eq!(
+            classify(&anyhow!("Metadata account not found")),
+            ErrorClass::Permanent
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde_json::Value;
+use solana_client::{
+    client_error::Result as ClientResult,
+    http_sender::HttpSender,
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+
+use crate::{
+    rate_limit::RateLimiter,
+    retry::{classify_client_error, ErrorClass},
+};
+
+/// How long an endpoint that errored or fell behind is skipped.
+const ENDPOINT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// How many slots an endpoint may trail the newest slot seen across the pool.
+const MAX_SLOT_LAG: u64 = 150;
+
+struct Endpoint {
+    sender: HttpSender,
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn is_healthy(&self) -> bool {
+        match *self.unhealthy_until.lock().unwrap() {
+            Some(until) => Instant::now() >= until,
+            None => true,
+        }
+    }
+
+    fn mark_unhealthy(&self) {
+        *self.unhealthy_until.lock().unwrap() = Some(Instant::now() + ENDPOINT_COOLDOWN);
+    }
+}
+
+/// Transport over one or more RPC endpoints. Reads are spread round-robin over
+/// the healthy endpoints and fail over to the next one when an endpoint errors
+/// or lags behind; transactions are broadcast to every healthy endpoint. An
+/// optional [`RateLimiter`] is shared by all of them.
+pub struct RpcPool {
+    endpoints: Vec<Endpoint>,
+    next: AtomicUsize,
+    max_slot: AtomicU64,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl RpcPool {
+    pub fn new(urls: Vec<String>, limiter: Option<Arc<RateLimiter>>) -> Self {
+        assert!(!urls.is_empty(), "RPC pool needs at least one endpoint");
+
+        let endpoints = urls
+            .into_iter()
+            .map(|url| Endpoint {
+                sender: HttpSender::new(url),
+                unhealthy_until: Mutex::new(None),
+            })
+            .collect();
+
+        Self {
+            endpoints,
+            next: AtomicUsize::new(0),
+            max_slot: AtomicU64::new(0),
+            limiter,
+        }
+    }
+
+    /// Healthy endpoints in round-robin order, followed by the unhealthy ones
+    /// as a last resort.
+    fn candidates(&self) -> Vec<&Endpoint> {
+        let len = self.endpoints.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+
+        let (healthy, unhealthy): (Vec<&Endpoint>, Vec<&Endpoint>) = (0..len)
+            .map(|i| &self.endpoints[(start + i) % len])
+            .partition(|endpoint| endpoint.is_healthy());
+
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    async fn send_to(
+        &self,
+        endpoint: &Endpoint,
+        request: RpcRequest,
+        params: Value,
+    ) -> ClientResult<Value> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+
+        endpoint.sender.send(request, params).await
+    }
+
+    /// Tracks the newest slot a response was served at and reports whether
+    /// this one came from an endpoint that is too far behind.
+    fn is_lagging(&self, response: &Value) -> bool {
+        let slot = match response
+            .get("context")
+            .and_then(|context| context.get("slot"))
+            .and_then(Value::as_u64)
+        {
+            Some(slot) => slot,
+            None => return false,
+        };
+
+        let max_slot = self.max_slot.fetch_max(slot, Ordering::Relaxed).max(slot);
+
+        slot + MAX_SLOT_LAG < max_slot
+    }
+
+    async fn broadcast(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let mut endpoints: Vec<&Endpoint> = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.is_healthy())
+            .collect();
+        if endpoints.is_empty() {
+            endpoints = self.endpoints.iter().collect();
+        }
+
+        let results = join_all(
+            endpoints
+                .iter()
+                .map(|endpoint| self.send_to(endpoint, request, params.clone())),
+        )
+        .await;
+
+        // Every endpoint returns the same signature, so any success will do.
+        let mut first_error = None;
+        for (endpoint, result) in endpoints.into_iter().zip(results) {
+            match result {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    if classify_client_error(e.kind()) == ErrorClass::Transient {
+                        endpoint.mark_unhealthy();
+                    }
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        Err(first_error.expect("broadcast to at least one endpoint"))
+    }
+}
+
+#[async_trait]
+impl RpcSender for RpcPool {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        if matches!(request, RpcRequest::SendTransaction) {
+            return self.broadcast(request, params).await;
+        }
+
+        let mut stale = None;
+        let mut last_error = None;
+
+        for endpoint in self.candidates() {
+            match self.send_to(endpoint, request, params.clone()).await {
+                Ok(response) if self.is_lagging(&response) => {
+                    endpoint.mark_unhealthy();
+                    stale = Some(response);
+                }
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    // Only transient errors put the endpoint on cooldown, but
+                    // any error is worth another endpoint's opinion: providers
+                    // differ in the methods and limits they allow.
+                    if classify_client_error(e.kind()) == ErrorClass::Transient {
+                        endpoint.mark_unhealthy();
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        // A lagging answer beats none at all.
+        match (stale, last_error) {
+            (Some(response), _) => Ok(response),
+            (None, Some(e)) => Err(e),
+            (None, None) => unreachable!("RPC pool has at least one endpoint"),
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let mut stats = RpcTransportStats::default();
+
+        for endpoint in &self.endpoints {
+            let endpoint_stats = endpoint.sender.get_transport_stats();
+            stats.request_count += endpoint_stats.request_count;
+            stats.elapsed_time += endpoint_stats.elapsed_time;
+            stats.rate_limited_time += endpoint_stats.rate_limited_time;
+        }
+
+        stats
+    }
+
+    fn url(&self) -> String {
+        self.endpoints[0].sender.url()
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    fmt,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use console::style;
+use serde::Serialize;
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+
+use crate::say;
+
+/// The lifecycle steps `goose run` works through, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStep {
+    InitSigner,
+    Initialize,
+    Start,
+    WaitForUnlock,
+    Migrate,
+    Check,
+}
+
+impl fmt::Display for RunStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunStep::InitSigner => write!(f, "init-signer"),
+            RunStep::Initialize => write!(f, "init"),
+            RunStep::Start => write!(f, "start"),
+            RunStep::WaitForUnlock => write!(f, "wait-for-unlock"),
+            RunStep::Migrate => write!(f, "migrate"),
+            RunStep::Check => write!(f, "check"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    /// Already done on chain.
+    Skipped,
+    Completed,
+    /// Only simulated because of `--dry-run`.
+    Simulated,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepRecord {
+    step: RunStep,
+    status: StepStatus,
+    started_at: String,
+    finished_at: String,
+    detail: Value,
+}
+
+/// Record of a `goose run`, rewritten after every step so an interrupted run
+/// still leaves a report behind.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    collection_mint: String,
+    started_at: String,
+    finished_at: Option<String>,
+    steps: Vec<StepRecord>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+pub fn run_report_path(collection_mint: &Pubkey) -> PathBuf {
+    PathBuf::from(format!("{collection_mint}_run_report.json"))
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl RunReport {
+    pub fn new(collection_mint: &Pubkey) -> Self {
+        Self {
+            collection_mint: collection_mint.to_string(),
+            started_at: timestamp(Utc::now()),
+            finished_at: None,
+            steps: Vec::new(),
+            path: run_report_path(collection_mint),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records the result of a step. A failed step is recorded with its error,
+    /// which is then passed on to the caller.
+    pub fn step(
+        &mut self,
+        step: RunStep,
+        started_at: DateTime<Utc>,
+        result: Result<(StepStatus, Value)>,
+    ) -> Result<StepStatus> {
+        let (status, detail, error) = match result {
+            Ok((status, detail)) => (status, detail, None),
+            Err(e) => (
+                StepStatus::Failed,
+                json!({ "error": e.to_string() }),
+                Some(e),
+            ),
+        };
+
+        let label = match status {
+            StepStatus::Skipped => style("skipped, already done").dim(),
+            StepStatus::Completed => style("completed").green(),
+            StepStatus::Simulated => style("simulated").yellow(),
+            StepStatus::Failed => style("failed").red(),
+        };
+        say!("{} {}", style(format!("[{step}]")).bold(), label);
+
+        self.steps.push(StepRecord {
+            step,
+            status,
+            started_at: timestamp(started_at),
+            finished_at: timestamp(Utc::now()),
+            detail,
+        });
+        self.save()?;
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(status),
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        self.finished_at = Some(timestamp(Utc::now()));
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let f = File::create(&self.path)?;
+        serde_json::to_writer_pretty(f, self)?;
+
+        Ok(())
+    }
+}
@@ -1,14 +1,24 @@
 use anyhow::{anyhow, Result};
 use dirs::home_dir;
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient as AsyncRpcClient,
+    rpc_client::{RpcClient, RpcClientConfig},
+};
 use solana_sdk::{
     clock::Slot,
     commitment_config::CommitmentConfig,
     hash::Hash,
+    pubkey::Pubkey,
     signature::{read_keypair_file, Keypair},
+    signer::Signer,
+};
+use std::{fs::File, path::PathBuf, str::FromStr, sync::Arc};
+
+use crate::{
+    compute_budget::ComputeBudget, nonce::DurableNonce, rate_limit::RateLimiter, rpc_pool::RpcPool,
+    squads::SquadsProposal,
 };
-use std::{fs::File, path::PathBuf, str::FromStr};
 
 #[derive(Debug, Deserialize, Serialize)]
 struct SolanaConfig {
@@ -17,52 +27,138 @@ struct SolanaConfig {
     pub commitment: String,
 }
 
+/// Settings only goose reads, kept apart from the Solana CLI config, which
+/// drops unknown keys whenever `solana config set` rewrites it.
+#[derive(Debug, Deserialize, Serialize)]
+struct GooseConfig {
+    /// Endpoints to pool with failover, used instead of the Solana CLI's
+    /// `json_rpc_url`.
+    #[serde(default)]
+    pub rpc_urls: Vec<String>,
+}
+
+/// Options shared by every command, taken from the global CLI arguments.
+#[derive(Clone, Debug, Default)]
+pub struct GlobalOptions {
+    pub keypair_path: Option<PathBuf>,
+    pub fee_payer_path: Option<PathBuf>,
+    pub rpc_urls: Vec<String>,
+    pub compute_budget: ComputeBudget,
+    pub dry_run: bool,
+    pub nonce_account: Option<Pubkey>,
+    pub nonce_authority: Option<Pubkey>,
+    pub multisig: Option<Pubkey>,
+    pub vault_index: u8,
+}
+
 pub struct CliConfig {
     pub client: RpcClient,
+    pub rpc_urls: Vec<String>,
     pub keypair: Keypair,
+    pub fee_payer: Option<Keypair>,
     pub recent_blockhash: Hash,
     pub recent_slot: Slot,
+    pub compute_budget: ComputeBudget,
+    pub dry_run: bool,
+    pub nonce_account: Option<Pubkey>,
+    pub nonce_authority: Option<Pubkey>,
+    pub multisig: Option<Pubkey>,
+    pub vault_index: u8,
 }
 
 #[derive(Debug, Default)]
 pub struct CliConfigBuilder {
-    pub json_rpc_url: Option<String>,
+    pub json_rpc_urls: Vec<String>,
     pub keypair_path: Option<PathBuf>,
+    pub fee_payer_path: Option<PathBuf>,
     pub commitment: Option<String>,
+    pub compute_budget: ComputeBudget,
+    pub dry_run: bool,
+    pub nonce_account: Option<Pubkey>,
+    pub nonce_authority: Option<Pubkey>,
+    pub multisig: Option<Pubkey>,
+    pub vault_index: u8,
 }
 
 impl CliConfigBuilder {
     pub fn new() -> Self {
         Self {
-            json_rpc_url: None,
+            json_rpc_urls: Vec::new(),
             keypair_path: None,
+            fee_payer_path: None,
             commitment: None,
+            compute_budget: ComputeBudget::default(),
+            dry_run: false,
+            nonce_account: None,
+            nonce_authority: None,
+            multisig: None,
+            vault_index: 0,
         }
     }
     pub fn rpc_url(mut self, json_rpc_url: String) -> Self {
-        self.json_rpc_url = Some(json_rpc_url);
+        self.json_rpc_urls = vec![json_rpc_url];
+        self
+    }
+    pub fn rpc_urls(mut self, json_rpc_urls: Vec<String>) -> Self {
+        self.json_rpc_urls = json_rpc_urls;
         self
     }
     pub fn keypair_path(mut self, keypair_path: PathBuf) -> Self {
         self.keypair_path = Some(keypair_path);
         self
     }
+    pub fn fee_payer_path(mut self, fee_payer_path: PathBuf) -> Self {
+        self.fee_payer_path = Some(fee_payer_path);
+        self
+    }
     pub fn commitment(mut self, commitment: String) -> Self {
         self.commitment = Some(commitment);
         self
     }
-    pub fn build(&self) -> Result<CliConfig> {
-        let rpc_url = self
-            .json_rpc_url
-            .clone()
-            .ok_or_else(|| anyhow!("No rpc url provided"))?;
+    pub fn compute_budget(mut self, compute_budget: ComputeBudget) -> Self {
+        self.compute_budget = compute_budget;
+        self
+    }
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+    pub fn nonce(mut self, nonce_account: Option<Pubkey>, nonce_authority: Option<Pubkey>) -> Self {
+        self.nonce_account = nonce_account;
+        self.nonce_authority = nonce_authority;
+        self
+    }
+    pub fn multisig(mut self, multisig: Option<Pubkey>, vault_index: u8) -> Self {
+        self.multisig = multisig;
+        self.vault_index = vault_index;
+        self
+    }
+    fn client(&self) -> Result<RpcClient> {
+        if self.json_rpc_urls.is_empty() {
+            return Err(anyhow!("No rpc url provided"));
+        }
 
         let commitment = match self.commitment.clone() {
             Some(commitment) => CommitmentConfig::from_str(&commitment)?,
             None => CommitmentConfig::confirmed(),
         };
 
-        let client = RpcClient::new_with_commitment(rpc_url, commitment);
+        let client = match self.json_rpc_urls.as_slice() {
+            [rpc_url] => RpcClient::new_with_commitment(rpc_url.clone(), commitment),
+            rpc_urls => RpcClient::new_sender(
+                RpcPool::new(rpc_urls.to_vec(), None),
+                RpcClientConfig::with_commitment(commitment),
+            ),
+        };
+
+        Ok(client)
+    }
+    pub fn build(&self) -> Result<CliConfig> {
+        let client = self.client()?;
+        if self.nonce_authority.is_some() && self.nonce_account.is_none() {
+            return Err(anyhow!("A nonce authority requires a nonce account"));
+        }
+        let rpc_urls = self.json_rpc_urls.clone();
 
         let keypair_path = self
             .keypair_path
@@ -72,50 +168,169 @@ impl CliConfigBuilder {
         let keypair =
             read_keypair_file(keypair_path).map_err(|_| anyhow!("Unable to read keypair file"))?;
 
+        let fee_payer = match &self.fee_payer_path {
+            Some(fee_payer_path) => Some(
+                read_keypair_file(fee_payer_path)
+                    .map_err(|_| anyhow!("Unable to read fee payer keypair file"))?,
+            ),
+            None => None,
+        };
+
         let recent_blockhash = client.get_latest_blockhash()?;
         let recent_slot = client.get_slot()?;
 
         Ok(CliConfig {
             client,
+            rpc_urls,
             keypair,
+            fee_payer,
             recent_blockhash,
             recent_slot,
+            compute_budget: self.compute_budget,
+            dry_run: self.dry_run,
+            nonce_account: self.nonce_account,
+            nonce_authority: self.nonce_authority,
+            multisig: self.multisig,
+            vault_index: self.vault_index,
         })
     }
 }
 
 impl CliConfig {
-    pub fn new(keypair_path: Option<PathBuf>, rpc_url: Option<String>) -> Result<Self> {
-        let mut builder = CliConfigBuilder::new();
-        let solana_config = parse_solana_config();
-
-        if let Some(config) = solana_config {
-            builder = builder
-                .rpc_url(config.json_rpc_url)
-                .keypair_path(config.keypair_path.into())
-                .commitment(config.commitment);
+    pub fn new(options: &GlobalOptions) -> Result<Self> {
+        let config = config_builder(options).build()?;
+
+        Ok(config)
+    }
+
+    /// Nonblocking client for the same endpoints and commitment, for use inside
+    /// async tasks.
+    pub fn async_client(&self) -> AsyncRpcClient {
+        self.limited_async_client(None)
+    }
+
+    /// Like [`CliConfig::async_client`], but every request first takes a token
+    /// from `limiter` when one is given.
+    pub fn limited_async_client(&self, limiter: Option<Arc<RateLimiter>>) -> AsyncRpcClient {
+        match (self.rpc_urls.as_slice(), limiter) {
+            ([rpc_url], None) => {
+                AsyncRpcClient::new_with_commitment(rpc_url.clone(), self.client.commitment())
+            }
+            (_, limiter) => AsyncRpcClient::new_sender(
+                RpcPool::new(self.rpc_urls.clone(), limiter),
+                RpcClientConfig::with_commitment(self.client.commitment()),
+            ),
         }
+    }
 
-        if let Some(keypair_path) = keypair_path {
-            builder = builder.keypair_path(keypair_path);
+    /// Pays the transaction fees and rent: the `--fee-payer` keypair if one was
+    /// given, otherwise the authority keypair.
+    pub fn payer(&self) -> &Keypair {
+        self.fee_payer.as_ref().unwrap_or(&self.keypair)
+    }
+
+    /// Fetches the durable nonce to sign with, if one was requested. The nonce
+    /// authority defaults to the fee payer and may also be the authority
+    /// keypair, since both sign every transaction that uses a nonce.
+    pub fn durable_nonce(&self) -> Result<Option<DurableNonce>> {
+        let account = match self.nonce_account {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        let payer = self.payer().pubkey();
+        let keypair = self.keypair.pubkey();
+        let authority = self.nonce_authority.unwrap_or(payer);
+
+        if authority != payer && authority != keypair {
+            return Err(anyhow!(
+                "Nonce authority {authority} must be the fee payer {payer} or the authority {keypair}"
+            ));
         }
 
-        if let Some(rpc_url) = rpc_url {
-            builder = builder.rpc_url(rpc_url);
+        DurableNonce::fetch(&self.client, account, authority).map(Some)
+    }
+
+    /// Looks up the next vault transaction when authority actions should be
+    /// proposed to a Squads multisig.
+    pub fn squads_proposal(&self) -> Result<Option<SquadsProposal>> {
+        match self.multisig {
+            Some(multisig) => {
+                SquadsProposal::fetch(&self.client, multisig, self.vault_index).map(Some)
+            }
+            None => Ok(None),
         }
+    }
+}
 
-        let config = builder.build()?;
+/// Reads the keypair from the options or the Solana CLI config without
+/// connecting to an RPC server, for signing on offline machines.
+pub fn load_keypair(options: &GlobalOptions) -> Result<Keypair> {
+    let keypair_path = match options.keypair_path.clone() {
+        Some(keypair_path) => keypair_path,
+        None => parse_solana_config()
+            .map(|config| PathBuf::from(config.keypair_path))
+            .ok_or_else(|| anyhow!("No keypair path provided"))?,
+    };
 
-        Ok(config)
+    read_keypair_file(keypair_path).map_err(|_| anyhow!("Unable to read keypair file"))
+}
+
+/// RPC client for the endpoints and commitment `CliConfig` would use, for
+/// commands that don't need a keypair.
+pub fn rpc_client(options: &GlobalOptions) -> Result<RpcClient> {
+    config_builder(options).client()
+}
+
+/// Fetches the durable nonce for a message that is signed elsewhere, so its
+/// authority doesn't have to be the local keypair.
+pub fn message_nonce(
+    client: &RpcClient,
+    options: &GlobalOptions,
+    default_authority: Pubkey,
+) -> Result<Option<DurableNonce>> {
+    let account = match options.nonce_account {
+        Some(account) => account,
+        None => return Ok(None),
+    };
+    let authority = options.nonce_authority.unwrap_or(default_authority);
+
+    DurableNonce::fetch(client, account, authority).map(Some)
+}
+
+fn config_builder(options: &GlobalOptions) -> CliConfigBuilder {
+    let mut builder = CliConfigBuilder::new()
+        .compute_budget(options.compute_budget)
+        .dry_run(options.dry_run)
+        .nonce(options.nonce_account, options.nonce_authority)
+        .multisig(options.multisig, options.vault_index);
+    let solana_config = parse_solana_config();
+
+    if let Some(config) = solana_config {
+        builder = builder
+            .rpc_url(config.json_rpc_url)
+            .keypair_path(config.keypair_path.into())
+            .commitment(config.commitment);
     }
 
-    #[allow(unused)]
-    pub fn update_blocks(&mut self) -> Result<()> {
-        self.recent_blockhash = self.client.get_latest_blockhash()?;
-        self.recent_slot = self.client.get_slot()?;
+    if let Some(config) = parse_goose_config() {
+        if !config.rpc_urls.is_empty() {
+            builder = builder.rpc_urls(config.rpc_urls);
+        }
+    }
+
+    if let Some(keypair_path) = options.keypair_path.clone() {
+        builder = builder.keypair_path(keypair_path);
+    }
 
-        Ok(())
+    if let Some(fee_payer_path) = options.fee_payer_path.clone() {
+        builder = builder.fee_payer_path(fee_payer_path);
     }
+
+    if !options.rpc_urls.is_empty() {
+        builder = builder.rpc_urls(options.rpc_urls.clone());
+    }
+
+    builder
 }
 
 fn parse_solana_config() -> Option<SolanaConfig> {
@@ -135,3 +350,13 @@ fn parse_solana_config() -> Option<SolanaConfig> {
     }
     None
 }
+
+fn parse_goose_config() -> Option<GooseConfig> {
+    let home_path = home_dir().expect("Couldn't find home dir");
+
+    let goose_config_path = home_path.join(".config").join("goose").join("config.yml");
+
+    let config_file = File::open(goose_config_path).ok()?;
+
+    serde_yaml::from_reader(config_file).ok()
+}
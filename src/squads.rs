@@ -0,0 +1,384 @@
+use anyhow::{anyhow, bail, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_client::rpc_client::RpcClient;
+use solana_program::{
+    hash::hash,
+    instruction::{AccountMeta, CompiledInstruction, Instruction},
+    message::Message,
+    pubkey,
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Squads v4 multisig program.
+pub const SQUADS_PROGRAM_ID: Pubkey = pubkey!("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf");
+
+const SEED_PREFIX: &[u8] = b"multisig";
+const SEED_VAULT: &[u8] = b"vault";
+const SEED_TRANSACTION: &[u8] = b"transaction";
+const SEED_PROPOSAL: &[u8] = b"proposal";
+
+/// Offset of `transaction_index` in a multisig account: the Anchor
+/// discriminator, `create_key`, `config_authority`, `threshold` and `time_lock`.
+const TRANSACTION_INDEX_OFFSET: usize = 8 + 32 + 32 + 2 + 4;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct VaultTransactionCreateArgs {
+    vault_index: u8,
+    ephemeral_signers: u8,
+    transaction_message: Vec<u8>,
+    memo: Option<String>,
+}
+
+#[derive(BorshSerialize)]
+struct ProposalCreateArgs {
+    transaction_index: u64,
+    draft: bool,
+}
+
+/// The next vault transaction of a Squads multisig, whose vault stands in for
+/// the authority of the migration instructions.
+#[derive(Clone, Copy, Debug)]
+pub struct SquadsProposal {
+    pub multisig: Pubkey,
+    pub vault_index: u8,
+    pub transaction_index: u64,
+}
+
+/// The instructions of a vault transaction, decoded from its `TransactionMessage`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VaultTransaction {
+    pub vault_index: u8,
+    pub account_keys: Vec<Pubkey>,
+    pub instructions: Vec<CompiledInstruction>,
+}
+
+/// Anchor's 8 byte discriminator, e.g. `global:proposal_create` for an instruction.
+fn discriminator(preimage: &str) -> [u8; 8] {
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash(preimage.as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+fn instruction_data<T: BorshSerialize>(name: &str, args: &T) -> Result<Vec<u8>> {
+    let mut data = discriminator(&format!("global:{name}")).to_vec();
+    data.extend(args.try_to_vec()?);
+    Ok(data)
+}
+
+/// Writes the `u8` length prefix Squads uses for most of its message vectors.
+fn push_len(bytes: &mut Vec<u8>, len: usize) -> Result<()> {
+    let len = u8::try_from(len).map_err(|_| anyhow!("Too many entries for a vault transaction"))?;
+    bytes.push(len);
+    Ok(())
+}
+
+/// Compiles the instructions into the `TransactionMessage` layout stored in a
+/// Squads vault transaction, with the vault as fee payer.
+fn compile_transaction_message(vault: &Pubkey, instructions: &[Instruction]) -> Result<Vec<u8>> {
+    let message = Message::new(instructions, Some(vault));
+    let header = message.header;
+
+    let num_keys = u8::try_from(message.account_keys.len())
+        .map_err(|_| anyhow!("Too many accounts for a vault transaction"))?;
+    let num_signers = header.num_required_signatures;
+    let num_writable_signers = num_signers - header.num_readonly_signed_accounts;
+    let num_writable_non_signers = num_keys - num_signers - header.num_readonly_unsigned_accounts;
+
+    let mut bytes = vec![
+        num_signers,
+        num_writable_signers,
+        num_writable_non_signers,
+        num_keys,
+    ];
+    for key in &message.account_keys {
+        bytes.extend_from_slice(key.as_ref());
+    }
+
+    push_len(&mut bytes, message.instructions.len())?;
+    for instruction in &message.instructions {
+        bytes.push(instruction.program_id_index);
+        push_len(&mut bytes, instruction.accounts.len())?;
+        bytes.extend_from_slice(&instruction.accounts);
+
+        let data_len = u16::try_from(instruction.data.len())
+            .map_err(|_| anyhow!("Instruction data too long for a vault transaction"))?;
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        bytes.extend_from_slice(&instruction.data);
+    }
+
+    // No address lookup tables.
+    bytes.push(0);
+
+    Ok(bytes)
+}
+
+/// Splits `len` bytes off the front of `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if bytes.len() < len {
+        bail!("Vault transaction message is truncated");
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn take_u8(bytes: &mut &[u8]) -> Result<u8> {
+    Ok(take(bytes, 1)?[0])
+}
+
+/// Inverse of [`compile_transaction_message`]. Address lookup tables are not
+/// resolved, so instructions using them reference keys past `account_keys`.
+fn decode_transaction_message(mut bytes: &[u8]) -> Result<(Vec<Pubkey>, Vec<CompiledInstruction>)> {
+    let bytes = &mut bytes;
+
+    // Skip the signer and writable counts.
+    take(bytes, 3)?;
+    let num_keys = take_u8(bytes)?;
+    let account_keys = (0..num_keys)
+        .map(|_| Ok(Pubkey::new_from_array(take(bytes, 32)?.try_into()?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let num_instructions = take_u8(bytes)?;
+    let instructions = (0..num_instructions)
+        .map(|_| {
+            let program_id_index = take_u8(bytes)?;
+            let num_accounts = take_u8(bytes)?;
+            let accounts = take(bytes, num_accounts as usize)?.to_vec();
+            let data_len = u16::from_le_bytes(take(bytes, 2)?.try_into()?);
+            let data = take(bytes, data_len as usize)?.to_vec();
+
+            Ok(CompiledInstruction {
+                program_id_index,
+                accounts,
+                data,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((account_keys, instructions))
+}
+
+/// Decodes the data of a `vault_transaction_create` instruction, or returns
+/// `None` for other Squads instructions.
+pub fn decode_vault_transaction_create(data: &[u8]) -> Result<Option<VaultTransaction>> {
+    let args = match data.strip_prefix(&discriminator("global:vault_transaction_create")) {
+        Some(args) => args,
+        None => return Ok(None),
+    };
+    let args = VaultTransactionCreateArgs::try_from_slice(args)?;
+    let (account_keys, instructions) = decode_transaction_message(&args.transaction_message)?;
+
+    Ok(Some(VaultTransaction {
+        vault_index: args.vault_index,
+        account_keys,
+        instructions,
+    }))
+}
+
+impl SquadsProposal {
+    /// Reads the multisig to find the index the new vault transaction will get.
+    pub fn fetch(client: &RpcClient, multisig: Pubkey, vault_index: u8) -> Result<Self> {
+        let account = client.get_account(&multisig)?;
+
+        if account.owner != SQUADS_PROGRAM_ID
+            || !account.data.starts_with(&discriminator("account:Multisig"))
+        {
+            bail!("{multisig} is not a Squads multisig");
+        }
+
+        let index_bytes = account
+            .data
+            .get(TRANSACTION_INDEX_OFFSET..TRANSACTION_INDEX_OFFSET + 8)
+            .ok_or_else(|| anyhow!("Multisig account {multisig} is too short"))?;
+        let transaction_index = u64::from_le_bytes(index_bytes.try_into()?);
+
+        Ok(Self {
+            multisig,
+            vault_index,
+            transaction_index: transaction_index + 1,
+        })
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                SEED_PREFIX,
+                self.multisig.as_ref(),
+                SEED_VAULT,
+                &[self.vault_index],
+            ],
+            &SQUADS_PROGRAM_ID,
+        )
+        .0
+    }
+
+    pub fn transaction(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                SEED_PREFIX,
+                self.multisig.as_ref(),
+                SEED_TRANSACTION,
+                &self.transaction_index.to_le_bytes(),
+            ],
+            &SQUADS_PROGRAM_ID,
+        )
+        .0
+    }
+
+    pub fn proposal(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                SEED_PREFIX,
+                self.multisig.as_ref(),
+                SEED_TRANSACTION,
+                &self.transaction_index.to_le_bytes(),
+                SEED_PROPOSAL,
+            ],
+            &SQUADS_PROGRAM_ID,
+        )
+        .0
+    }
+
+    /// Replaces the instructions, which must use the vault as their authority,
+    /// with ones creating a vault transaction for them and an active proposal
+    /// members can vote on. `creator` has to be a member allowed to initiate.
+    pub fn wrap(
+        &self,
+        creator: &Pubkey,
+        rent_payer: &Pubkey,
+        instructions: &[Instruction],
+    ) -> Result<Vec<Instruction>> {
+        let transaction_create = Instruction {
+            program_id: SQUADS_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(self.multisig, false),
+                AccountMeta::new(self.transaction(), false),
+                AccountMeta::new_readonly(*creator, true),
+                AccountMeta::new(*rent_payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction_data(
+                "vault_transaction_create",
+                &VaultTransactionCreateArgs {
+                    vault_index: self.vault_index,
+                    ephemeral_signers: 0,
+                    transaction_message: compile_transaction_message(&self.vault(), instructions)?,
+                    memo: None,
+                },
+            )?,
+        };
+
+        let proposal_create = Instruction {
+            program_id: SQUADS_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(self.multisig, false),
+                AccountMeta::new(self.proposal(), false),
+                AccountMeta::new_readonly(*creator, true),
+                AccountMeta::new(*rent_payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction_data(
+                "proposal_create",
+                &ProposalCreateArgs {
+                    transaction_index: self.transaction_index,
+                    draft: false,
+                },
+            )?,
+        };
+
+        Ok(vec![transaction_create, proposal_create])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_message_with_vault_as_fee_payer() {
+        let vault = Pubkey::new_unique();
+        let state = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[7, 8, 9],
+            vec![
+                AccountMeta::new_readonly(vault, true),
+                AccountMeta::new(state, false),
+            ],
+        );
+
+        let bytes = compile_transaction_message(&vault, &[instruction]).unwrap();
+
+        // One writable signer (the vault), one writable non-signer, then the
+        // keys in message order: vault, state, program.
+        let mut expected = vec![1, 1, 1, 3];
+        expected.extend_from_slice(vault.as_ref());
+        expected.extend_from_slice(state.as_ref());
+        expected.extend_from_slice(program_id.as_ref());
+        // A single instruction calling key 2 with accounts 0 and 1.
+        expected.extend_from_slice(&[1, 2, 2, 0, 1, 3, 0, 7, 8, 9]);
+        // No address lookup tables.
+        expected.push(0);
+
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn decodes_wrapped_instructions() {
+        let proposal = SquadsProposal {
+            multisig: Pubkey::new_unique(),
+            vault_index: 1,
+            transaction_index: 5,
+        };
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[7, 8, 9],
+            vec![
+                AccountMeta::new_readonly(proposal.vault(), true),
+                AccountMeta::new(Pubkey::new_unique(), false),
+            ],
+        );
+        let creator = Pubkey::new_unique();
+
+        let wrapped = proposal
+            .wrap(&creator, &creator, &[instruction.clone()])
+            .unwrap();
+        let decoded = decode_vault_transaction_create(&wrapped[0].data)
+            .unwrap()
+            .unwrap();
+
+        let message = Message::new(&[instruction], Some(&proposal.vault()));
+        assert_eq!(
+            decoded,
+            VaultTransaction {
+                vault_index: 1,
+                account_keys: message.account_keys,
+                instructions: message.instructions,
+            }
+        );
+        assert!(decode_vault_transaction_create(&wrapped[1].data)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2], vec![]);
+        let bytes = compile_transaction_message(&Pubkey::new_unique(), &[instruction]).unwrap();
+
+        // Cut into the instruction data.
+        assert!(decode_transaction_message(&bytes[..bytes.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn rejects_more_keys_than_fit_a_u8() {
+        let accounts = (0..256)
+            .map(|_| AccountMeta::new_readonly(Pubkey::new_unique(), false))
+            .collect();
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[], accounts);
+
+        assert!(compile_transaction_message(&Pubkey::new_unique(), &[instruction]).is_err());
+    }
+}
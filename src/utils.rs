@@ -1,14 +1,31 @@
 use std::{str::FromStr, time::Duration};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, Result};
 use indicatif::{ProgressBar, ProgressStyle};
+use mpl_token_metadata::state::{Metadata, TokenMetadataAccount, TokenStandard};
 use serde::Deserialize;
 use serde_json::json;
-use solana_client::{rpc_client::RpcClient, rpc_request::RpcRequest};
-use solana_program::{pubkey, pubkey::Pubkey};
-use solana_sdk::hash::Hash;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient as AsyncRpcClient,
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_request::{RpcError, RpcRequest},
+};
+use solana_program::{
+    bpf_loader_upgradeable::UpgradeableLoaderState,
+    program_pack::Pack,
+    pubkey,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{self, clock::Clock},
+};
+use solana_sdk::{account::Account, hash::Hash};
+use spl_token::state::Mint;
 
-use crate::Cluster;
+use crate::{errors::TokenAccountError, output::is_json, Cluster};
 
 const TOKEN_METADATA_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 
@@ -23,6 +40,17 @@ pub fn find_metadata_pda(mint: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(seeds, &TOKEN_METADATA_ID)
 }
 
+pub async fn get_metadata_async(client: &AsyncRpcClient, mint: &Pubkey) -> Result<Metadata> {
+    let (metadata, _) = find_metadata_pda(mint);
+    let account = client.get_account_data(&metadata).await?;
+
+    Ok(Metadata::safe_deserialize(&account)?)
+}
+
+pub fn is_programmable(metadata: &Metadata) -> bool {
+    metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible)
+}
+
 pub fn find_migrate_state_pda(mint: &Pubkey) -> (Pubkey, u8) {
     let seeds = &[b"migration", mint.as_ref()];
     Pubkey::find_program_address(seeds, &mpl_migration_validator::ID)
@@ -32,6 +60,21 @@ pub fn find_program_signer_pda() -> (Pubkey, u8) {
     Pubkey::find_program_address(&[b"signer"], &mpl_migration_validator::ID)
 }
 
+/// Current unix timestamp according to the cluster's clock sysvar.
+pub fn get_cluster_time(client: &RpcClient) -> Result<i64> {
+    let data = client.get_account_data(&sysvar::clock::ID)?;
+    let clock: Clock = bincode::deserialize(&data)?;
+
+    Ok(clock.unix_timestamp)
+}
+
+pub async fn get_cluster_time_async(client: &AsyncRpcClient) -> Result<i64> {
+    let data = client.get_account_data(&sysvar::clock::ID).await?;
+    let clock: Clock = bincode::deserialize(&data)?;
+
+    Ok(clock.unix_timestamp)
+}
+
 pub fn get_cluster(rpc_client: &RpcClient) -> Result<Cluster> {
     let devnet_hash = Hash::from_str(DEVNET_HASH).unwrap();
     let mainnet_hash = Hash::from_str(MAINNET_HASH).unwrap();
@@ -47,6 +90,11 @@ pub fn get_cluster(rpc_client: &RpcClient) -> Result<Cluster> {
 }
 
 pub fn spinner_with_style() -> ProgressBar {
+    // Progress output would corrupt the JSON on stdout.
+    if is_json() {
+        return ProgressBar::hidden();
+    }
+
     let pb = ProgressBar::new_spinner();
     pb.enable_steady_tick(Duration::from_millis(100));
     pb.set_style(
@@ -66,33 +114,127 @@ pub fn spinner_with_style() -> ProgressBar {
     pb
 }
 
-pub fn get_nft_token_account(client: &RpcClient, mint: Pubkey) -> Result<Pubkey> {
-    let request = RpcRequest::Custom {
-        method: "getTokenLargestAccounts",
-    };
+// Layout of an SPL token account.
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const TOKEN_ACCOUNT_MINT_OFFSET: usize = 0;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+const LARGEST_ACCOUNTS_REQUEST: RpcRequest = RpcRequest::Custom {
+    method: "getTokenLargestAccounts",
+};
+
+// JSON RPC error code for an unknown method, which some providers also use for
+// methods they have disabled.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Resolves the token account holding a mint's single token. Falls back to
+/// scanning the token program when `getTokenLargestAccounts` is unavailable.
+pub async fn get_nft_token_account_async(client: &AsyncRpcClient, mint: Pubkey) -> Result<Pubkey> {
     let params = json!([mint.to_string(), { "commitment": "confirmed" }]);
-    let result: JRpcResponse = client.send(request, params)?;
 
-    let token_accounts: Vec<TokenAccount> = result
-        .value
-        .into_iter()
-        .filter(|account| account.amount.parse::<u64>().unwrap() == 1)
-        .collect();
-
-    if token_accounts.len() > 1 {
-        bail!(
-            "Mint account {} had more than one token account with 1 token",
-            mint
-        );
+    let holders = match client
+        .send::<JRpcResponse>(LARGEST_ACCOUNTS_REQUEST, params)
+        .await
+    {
+        Ok(result) => holder_accounts(result)?,
+        Err(e) if is_method_unavailable(&e) => {
+            let accounts = client
+                .get_program_accounts_with_config(&spl_token::ID, holder_accounts_config(&mint))
+                .await?;
+            accounts.into_iter().map(|(pubkey, _)| pubkey).collect()
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if holders.is_empty() {
+        let mint_account = client
+            .get_account_with_commitment(&mint, client.commitment())
+            .await?
+            .value;
+        return Err(missing_holder_error(mint, mint_account));
+    }
+
+    find_nft_token_account(mint, holders)
+}
+
+/// Whether the RPC server doesn't serve `getTokenLargestAccounts` at all, as
+/// opposed to failing this particular request.
+fn is_method_unavailable(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, message, .. }) => {
+            let message = message.to_lowercase();
+            *code == METHOD_NOT_FOUND
+                || message.contains("method not found")
+                || message.contains("disabled")
+        }
+        _ => false,
+    }
+}
+
+/// Token accounts from a `getTokenLargestAccounts` response holding exactly
+/// one token.
+fn holder_accounts(result: JRpcResponse) -> Result<Vec<Pubkey>> {
+    let mut holders = Vec::new();
+
+    for account in result.value {
+        let amount: u64 = account.amount.parse().map_err(|_| {
+            anyhow!(
+                "Invalid amount '{}' for token account {}",
+                account.amount,
+                account.address
+            )
+        })?;
+
+        if amount == 1 {
+            holders.push(Pubkey::from_str(&account.address)?);
+        }
     }
 
-    if token_accounts.is_empty() {
-        bail!("Mint account {} had zero token accounts with 1 token", mint);
+    Ok(holders)
+}
+
+/// Filters token accounts down to those of `mint` holding exactly one token.
+fn holder_accounts_config(mint: &Pubkey) -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(TOKEN_ACCOUNT_LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                TOKEN_ACCOUNT_MINT_OFFSET,
+                mint.as_ref(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                TOKEN_ACCOUNT_AMOUNT_OFFSET,
+                &1u64.to_le_bytes(),
+            )),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Tells a burned mint apart from one whose token account was closed or emptied.
+fn missing_holder_error(mint: Pubkey, mint_account: Option<Account>) -> anyhow::Error {
+    let mint_account = match mint_account {
+        Some(account) => account,
+        None => return TokenAccountError::MissingMint(mint).into(),
+    };
+
+    match Mint::unpack(&mint_account.data) {
+        Ok(state) if state.supply == 0 => TokenAccountError::Burned(mint).into(),
+        Ok(_) => TokenAccountError::Closed(mint).into(),
+        Err(e) => anyhow!("{mint} is not a token mint: {e}"),
     }
+}
 
-    let token_pubkey = Pubkey::from_str(&token_accounts[0].address)?;
+fn find_nft_token_account(mint: Pubkey, holders: Vec<Pubkey>) -> Result<Pubkey> {
+    if holders.len() > 1 {
+        return Err(TokenAccountError::MultipleHolders(mint, holders.len()).into());
+    }
 
-    Ok(token_pubkey)
+    Ok(holders[0])
 }
 
 #[derive(Debug, Deserialize)]
@@ -111,7 +253,44 @@ struct TokenAccount {
     // ui_amount_string: String,
 }
 
+/// Returns the program owning a token owner account and, if that program is
+/// upgradeable, its program data buffer. Owners without an account are wallets
+/// that have been emptied, so they count as owned by the system program.
+pub async fn get_token_owner_program_async(
+    client: &AsyncRpcClient,
+    token_owner: &Pubkey,
+) -> Result<(Pubkey, Option<Pubkey>)> {
+    let token_owner_program = match client
+        .get_account_with_commitment(token_owner, client.commitment())
+        .await?
+        .value
+    {
+        Some(account) => account.owner,
+        None => return Ok((system_program::ID, None)),
+    };
+
+    let token_owner_program_account = client.get_account(&token_owner_program).await?;
+
+    // We need to pass the program data buffer to the migration program
+    // if the token owner program is an upgradeable program.
+    let state_opt: Option<UpgradeableLoaderState> =
+        bincode::deserialize(&token_owner_program_account.data).ok();
+
+    let token_owner_program_buffer = match state_opt {
+        Some(UpgradeableLoaderState::Program {
+            programdata_address,
+        }) => Some(programdata_address),
+        _ => None,
+    };
+
+    Ok((token_owner_program, token_owner_program_buffer))
+}
+
 pub fn create_progress_bar(msg: &'static str, len: u64) -> ProgressBar {
+    if is_json() {
+        return ProgressBar::hidden();
+    }
+
     let pb = ProgressBar::new(len);
 
     let style = ProgressStyle::default_bar()
@@ -123,3 +302,72 @@ pub fn create_progress_bar(msg: &'static str, len: u64) -> ProgressBar {
     pb.set_message(msg);
     pb
 }
+
+#[cfg(test)]
+mod tests {
+    use solana_client::rpc_request::RpcResponseErrorData;
+
+    use super::*;
+
+    fn largest_accounts(amounts: &[(&Pubkey, &str)]) -> JRpcResponse {
+        let value: Vec<_> = amounts
+            .iter()
+            .map(|(address, amount)| json!({ "address": address.to_string(), "amount": amount }))
+            .collect();
+
+        serde_json::from_value(json!({ "value": value })).unwrap()
+    }
+
+    fn rpc_error(code: i64, message: &str) -> ClientError {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code,
+            message: message.to_string(),
+            data: RpcResponseErrorData::Empty,
+        })
+        .into()
+    }
+
+    #[test]
+    fn holder_accounts_keeps_accounts_with_one_token() {
+        let (holder, empty) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let holders = holder_accounts(largest_accounts(&[(&holder, "1"), (&empty, "0")])).unwrap();
+
+        assert_eq!(holders, vec![holder]);
+    }
+
+    #[test]
+    fn holder_accounts_rejects_invalid_amounts() {
+        let holder = Pubkey::new_unique();
+
+        assert!(holder_accounts(largest_accounts(&[(&holder, "1.0")])).is_err());
+    }
+
+    #[test]
+    fn only_missing_methods_fall_back() {
+        assert!(is_method_unavailable(&rpc_error(
+            METHOD_NOT_FOUND,
+            "Method not found"
+        )));
+        assert!(is_method_unavailable(&rpc_error(
+            -32000,
+            "getTokenLargestAccounts is disabled on this plan"
+        )));
+        assert!(!is_method_unavailable(&rpc_error(
+            -32005,
+            "Node is unhealthy"
+        )));
+    }
+
+    #[test]
+    fn missing_mint_is_not_reported_as_burned() {
+        let mint = Pubkey::new_unique();
+
+        let error = missing_holder_error(mint, None);
+
+        assert_eq!(
+            error.downcast_ref::<TokenAccountError>(),
+            Some(&TokenAccountError::MissingMint(mint))
+        );
+    }
+}